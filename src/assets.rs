@@ -1,13 +1,35 @@
+use std::path::{Path, PathBuf};
+
 pub use anyhow::*;
 use ldtk_easy::project::Project;
 pub use raylib::prelude::*;
 
+use crate::physics::PhysicsProfile;
+use crate::tile::TileSet;
+
+/// Where the levels are looked for when no path is given
+pub const LEVELS_PATH: &str = "levels.ldtk";
+/// Copy of the levels built into the game, for when there is no levels file around
+const EMBEDDED_LEVELS: &str = include_str!("../levels.ldtk");
+
 pub fn tuple2<T1: misc::AsF32, T2: misc::AsF32>(tuple: (T1, T2)) -> Vector2 {
     rvec2(tuple.0, tuple.1)
 }
 
+/// An LDtk project, together with what its IntGrid values stand for
+pub struct Levels {
+    pub project: Project,
+    pub tiles: TileSet,
+    /// Physics of levels that don't override them
+    pub physics: PhysicsProfile,
+    /// Fingerprint of the levels and physics, replays only play back on the ones they were recorded on
+    pub hash: u64,
+}
+
 pub struct Assets {
-    pub world: Project,
+    pub world: Levels,
+    /// File the levels were read from, `None` if the embedded copy is used
+    pub levels_path: Option<PathBuf>,
     pub player: Texture2D,
     pub tileset: Texture2D,
     pub numbers: Texture2D,
@@ -31,12 +53,18 @@ pub struct Assets {
 }
 
 impl Assets {
-    pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread) -> Result<Self> {
-        let world =
-            Project::new(include_str!("../levels.ldtk")).map_err(|err| anyhow!(err.message))?;
+    /// Reads the levels from `levels`, or from [`LEVELS_PATH`] if it exists
+    pub fn load(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        levels: Option<&Path>,
+    ) -> Result<Self> {
+        let levels_path = levels_path(levels);
+        let world = read_levels(levels_path.as_deref())?;
 
         Ok(Self {
             world,
+            levels_path,
             player: rl
                 .load_texture(thread, "Assets/Player.png")
                 .map_err(|err| anyhow!(err))?,
@@ -87,25 +115,99 @@ impl Assets {
     }
 }
 
-#[derive(PartialEq)]
+/// The levels file to use, `None` means the embedded copy
+pub fn levels_path(levels: Option<&Path>) -> Option<PathBuf> {
+    levels
+        .map(Path::to_path_buf)
+        .or_else(|| Some(PathBuf::from(LEVELS_PATH)).filter(|path| path.exists()))
+}
+
+/// Reads the levels without loading anything else, for tools that run without a window
+pub fn levels_text(levels: Option<&Path>) -> Result<String> {
+    match levels_path(levels) {
+        Some(path) => std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read levels from '{}'!", path.display())),
+        None => Ok(EMBEDDED_LEVELS.to_owned()),
+    }
+}
+
+/// Parses the levels in `text`, with the physics file in `dir` if there is one
+pub fn parse_levels(text: &str, dir: &Path) -> Result<Levels> {
+    let physics = PhysicsProfile::load(dir)?;
+    Ok(Levels {
+        project: Project::new(text).map_err(|err| anyhow!(err.message))?,
+        tiles: TileSet::parse(text)?,
+        hash: fnv1a([text.as_bytes(), &serde_json::to_vec(&physics)?]),
+        physics,
+    })
+}
+
+/// FNV-1a, unlike the std hashers it stays the same across builds so it can go into files
+fn fnv1a<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    parts
+        .into_iter()
+        .flatten()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// The levels built into the game, with the physics file where the levels file would be
+pub fn embedded_levels() -> Result<Levels> {
+    let dir = Path::new(LEVELS_PATH).parent().unwrap_or(Path::new(""));
+    parse_levels(EMBEDDED_LEVELS, dir)
+}
+
+pub fn load_levels(path: &Path) -> Result<Levels> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read levels from '{}'!", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse_levels(&text, dir).with_context(|| format!("Failed to parse '{}'!", path.display()))
+}
+
+/// Loads the levels from `levels`, [`LEVELS_PATH`] or the embedded copy, like the game does
+pub fn read_levels(levels: Option<&Path>) -> Result<Levels> {
+    match levels_path(levels) {
+        Some(path) => load_levels(&path),
+        None => embedded_levels(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum State {
     Playing,
     LevelTransition {
         next_level: usize,
         timer: f32,
+        /// Length of fading out and back in, the level is swapped in between
+        fade: f32,
         sound_played: bool,
         loaded: bool,
     },
     Paused,
+    Finished,
+    /// A hardcore run ended with a death
+    Failed,
 }
 
 impl State {
-    pub fn transition(next_level: usize) -> Self {
+    /// The screen stays visible for `fade` seconds first, so the player can see what happened
+    pub fn transition(next_level: usize, fade: f32) -> Self {
         Self::LevelTransition {
             next_level,
-            timer: 1.0,
+            timer: fade * 2.0,
+            fade,
             sound_played: false,
             loaded: false,
         }
     }
+
+    pub fn player_visible(&self) -> bool {
+        match self {
+            State::Playing => true,
+            State::LevelTransition { loaded, .. } => *loaded,
+            State::Paused => true,
+            State::Finished | State::Failed => false,
+        }
+    }
 }
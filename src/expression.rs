@@ -0,0 +1,166 @@
+use std::iter::Peekable;
+
+use crate::assets::*;
+
+/// Part of an expression, as it is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Number(u32),
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// Splits an expression like `12/4` into numbers and operators.
+/// `x`, `*` and `×` all multiply, `/` and `÷` both divide.
+pub fn parse(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '0'..='9' => {
+                let mut number = c.to_digit(10).unwrap_or_default();
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    number = number
+                        .checked_mul(10)
+                        .and_then(|number| number.checked_add(digit))
+                        .with_context(|| format!("Number in '{}' is too large!", text))?;
+                    chars.next();
+                }
+                Token::Number(number)
+            }
+            '+' => Token::Add,
+            '-' | '−' => Token::Subtract,
+            'x' | 'X' | '*' | '×' => Token::Multiply,
+            '/' | '÷' => Token::Divide,
+            c if c.is_whitespace() => continue,
+            c => bail!("Unexpected '{}' in expression '{}'!", c, text),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Works out the value of an expression, multiplying and dividing before adding and subtracting.
+/// Divisions have to come out even, so numbers stay whole.
+pub fn evaluate(tokens: &[Token]) -> Result<i32> {
+    let mut tokens = tokens.iter().copied().peekable();
+    let mut total = 0_i32;
+    let mut term = operand(&mut tokens)?;
+    while let Some(operator) = tokens.next() {
+        if let Token::Number(number) = operator {
+            bail!("Expected an operator before {}!", number);
+        }
+        let value = operand(&mut tokens)?;
+        term = match operator {
+            Token::Multiply => term.checked_mul(value),
+            Token::Divide if value == 0 => bail!("Division by zero!"),
+            Token::Divide if term.checked_rem(value) != Some(0) => {
+                bail!("{} can't be divided evenly by {}!", term, value)
+            }
+            Token::Divide => term.checked_div(value),
+            // Adding and subtracting wait until the term before them is finished
+            _ => {
+                total = total.checked_add(term).context("Result is too large!")?;
+                match operator {
+                    Token::Add => Some(value),
+                    _ => value.checked_neg(),
+                }
+            }
+        }
+        .context("Result is too large!")?;
+    }
+    total.checked_add(term).context("Result is too large!")
+}
+
+/// A number, with a minus in front if it's negative
+fn operand(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<i32> {
+    let negative = tokens.next_if_eq(&Token::Subtract).is_some();
+    let Some(Token::Number(number)) = tokens.next() else {
+        bail!("Expected a number!");
+    };
+    // Negated before narrowing, since -2147483648 fits but 2147483648 doesn't
+    let number = if negative {
+        -i64::from(number)
+    } else {
+        i64::from(number)
+    };
+    i32::try_from(number).context("Number is too large!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(text: &str) -> Result<i32> {
+        evaluate(&parse(text)?)
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            parse("12 / 4").unwrap(),
+            [Token::Number(12), Token::Divide, Token::Number(4)]
+        );
+        assert_eq!(
+            parse("3×2÷6−1").unwrap(),
+            [
+                Token::Number(3),
+                Token::Multiply,
+                Token::Number(2),
+                Token::Divide,
+                Token::Number(6),
+                Token::Subtract,
+                Token::Number(1),
+            ]
+        );
+        assert!(parse("2 ^ 3").is_err());
+        assert!(parse("99999999999").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(value("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(value("10 - 4 / 2").unwrap(), 8);
+        assert_eq!(value("2 * 3 - 4 * 5").unwrap(), -14);
+        assert_eq!(value("8 / 2 * 3").unwrap(), 12);
+    }
+
+    #[test]
+    fn negatives() {
+        assert_eq!(value("-5").unwrap(), -5);
+        assert_eq!(value("-7 + 2").unwrap(), -5);
+        assert_eq!(value("3 - -2").unwrap(), 5);
+        assert_eq!(value("2 * -3").unwrap(), -6);
+        assert_eq!(value("-6 / -2").unwrap(), 3);
+        assert_eq!(value("-2147483647 - 1").unwrap(), i32::MIN);
+        assert_eq!(value("-2147483648").unwrap(), i32::MIN);
+        assert_eq!(value("1 + -2147483648").unwrap(), -2147483647);
+        assert!(value("2147483648").is_err());
+        assert!(value("--2147483648").is_err());
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(value("-8 / 4").unwrap(), -2);
+        assert_eq!(value("0 / 5").unwrap(), 0);
+        assert!(value("1 / 0").is_err());
+        assert!(value("7 / 2").is_err());
+        assert!(value("-7 / 2").is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(value("2147483647 + 1").is_err());
+        assert!(value("65536 * 65536").is_err());
+        assert!(value("2147483648").is_err());
+        assert!(value("-2147483647 - 2").is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        for text in ["", "1 +", "+ 1", "1 2", "--1", "2 * / 3"] {
+            assert!(value(text).is_err(), "{}", text);
+        }
+    }
+}
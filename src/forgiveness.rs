@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// Lives at the start of every level in [`Forgiveness::Lives`], and after running out of them
+pub const LIVES: u32 = 3;
+/// Seconds added to the timer for a mistake in [`Forgiveness::Penalty`]
+pub const PENALTY: f32 = 5.0;
+/// Wrong numbers are ignored for this long after a mistake that didn't end the attempt
+pub const MISTAKE_GRACE: f32 = 1.0;
+
+/// What happens when the player touches a wrong number.
+/// Best times are kept for every mode on its own, so they don't mix.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Forgiveness {
+    /// The level starts over
+    #[default]
+    Normal,
+    /// Every wrong number costs one of a few lives, the level only starts over once they're gone
+    Lives,
+    /// No dying, but the timer jumps ahead
+    Penalty,
+    /// A wrong number ends the whole run, other deaths like spikes only start the level over
+    Hardcore,
+}
+
+impl Forgiveness {
+    pub const ALL: [Self; 4] = [Self::Normal, Self::Lives, Self::Penalty, Self::Hardcore];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "Normal",
+            Self::Lives => "Lives",
+            Self::Penalty => "Time penalty",
+            Self::Hardcore => "Hardcore",
+        }
+    }
+
+    /// Used on the command line and in file names
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Lives => "lives",
+            Self::Penalty => "penalty",
+            Self::Hardcore => "hardcore",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Byte stored in replays
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL.get(byte as usize).copied()
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::*;
+use crate::save::{data_dir, log_failure, write_atomic};
+
+const MAGIC: &[u8; 4] = b"CTCG";
+pub const VERSION: u16 = 1;
+
+/// Which recorded run to race against
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GhostMode {
+    Off,
+    /// Best attempt of the current level
+    Level,
+    /// The personal best of the whole game
+    Run,
+}
+
+/// Where the player was and how it looked on one tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GhostFrame {
+    pub level: usize,
+    pub position: Vector2,
+    pub frame: i8,
+    pub visible: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Ghost {
+    pub frames: Vec<GhostFrame>,
+}
+
+impl Ghost {
+    pub fn level(&self) -> Option<usize> {
+        self.frames.first().map(|frame| frame.level)
+    }
+
+    /// Position and sprite frame at `tick`, interpolated towards the next tick
+    pub fn at(&self, tick: usize, alpha: f32) -> Option<(usize, Vector2, i8)> {
+        let frame = self.frames.get(tick)?;
+        if !frame.visible {
+            return None;
+        }
+        let previous = tick
+            .checked_sub(1)
+            .and_then(|tick| self.frames.get(tick))
+            .filter(|previous| previous.level == frame.level && previous.visible)
+            .unwrap_or(frame);
+        Some((
+            frame.level,
+            previous.position.lerp(frame.position, alpha),
+            frame.frame,
+        ))
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        for frame in &self.frames {
+            writer.write_all(&(frame.level as u32).to_le_bytes())?;
+            writer.write_all(&frame.position.x.to_le_bytes())?;
+            writer.write_all(&frame.position.y.to_le_bytes())?;
+            writer.write_all(&[frame.frame as u8, frame.visible as u8])?;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> Result<Self> {
+        fn read_u32(reader: &mut impl Read) -> Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "Not a ghost file!");
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        ensure!(
+            version == VERSION,
+            "Ghost version {} is not supported (expected {})!",
+            version,
+            VERSION
+        );
+
+        let mut frames = Vec::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let level = read_u32(&mut reader)? as usize;
+            let x = f32::from_bits(read_u32(&mut reader)?);
+            let y = f32::from_bits(read_u32(&mut reader)?);
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes)?;
+            frames.push(GhostFrame {
+                level,
+                position: rvec2(x, y),
+                frame: bytes[0] as i8,
+                visible: bytes[1] != 0,
+            });
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        write_atomic(path.as_ref(), &bytes)
+    }
+
+    /// Loads a ghost, or `None` if there is no ghost saved at `path` yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::read(std::io::BufReader::new(std::fs::File::open(path)?))
+            .map(Some)
+            .with_context(|| format!("Failed to read ghost '{}'!", path.display()))
+    }
+}
+
+/// Records ghosts while the simulation runs
+#[derive(Debug, Clone, Default)]
+pub struct GhostRecorder {
+    /// One frame per tick of the run timer
+    pub run: Ghost,
+    /// Frames since the current level was last (re)loaded
+    pub attempt: Ghost,
+    /// Attempts that made it to the next level, waiting to be compared against the best ones
+    pub finished: Vec<Ghost>,
+}
+
+/// Best ghosts saved on disk
+#[derive(Debug, Default)]
+pub struct Ghosts {
+    pub levels: Vec<Option<Ghost>>,
+    pub run: Option<Ghost>,
+}
+
+impl Ghosts {
+    fn level_path(level: usize) -> PathBuf {
+        data_dir()
+            .join("ghosts")
+            .join(format!("level_{}.ghost", level))
+    }
+
+    fn run_path() -> PathBuf {
+        data_dir().join("ghosts").join("run.ghost")
+    }
+
+    /// Broken ghosts are logged and treated like missing ones, they get replaced by the next best
+    pub fn load(level_count: usize) -> Self {
+        Self {
+            levels: (0..level_count)
+                .map(|level| log_failure(Ghost::load(Self::level_path(level))).flatten())
+                .collect(),
+            run: log_failure(Ghost::load(Self::run_path())).flatten(),
+        }
+    }
+
+    /// Keeps `attempt` if it beats the saved ghost of its level
+    pub fn submit_level(&mut self, attempt: Ghost) -> Result<()> {
+        let Some(level) = attempt.level() else {
+            return Ok(());
+        };
+        if level >= self.levels.len() {
+            self.levels.resize(level + 1, None);
+        }
+        if let Some(best) = &self.levels[level] {
+            if best.frames.len() <= attempt.frames.len() {
+                return Ok(());
+            }
+        }
+        attempt.save(Self::level_path(level))?;
+        self.levels[level] = Some(attempt);
+        Ok(())
+    }
+
+    /// Keeps `run` if it beats the saved whole-game ghost
+    pub fn submit_run(&mut self, run: Ghost) -> Result<()> {
+        if let Some(best) = &self.run {
+            if best.frames.len() <= run.frames.len() {
+                return Ok(());
+            }
+        }
+        run.save(Self::run_path())?;
+        self.run = Some(run);
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use crate::assets::*;
+
+/// Something the player wants to do, independent of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Left,
+    Right,
+    Jump,
+    Grab,
+    Pause,
+    Restart,
+    // Menus only, the game never sees these
+    Up,
+    Down,
+    Confirm,
+    Back,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::Left,
+        Action::Right,
+        Action::Jump,
+        Action::Grab,
+        Action::Pause,
+        Action::Restart,
+        Action::Up,
+        Action::Down,
+        Action::Confirm,
+        Action::Back,
+    ];
+    /// Actions of the game itself, the only ones kept in replays
+    pub const GAME: [Action; 6] = [
+        Action::Left,
+        Action::Right,
+        Action::Jump,
+        Action::Grab,
+        Action::Pause,
+        Action::Restart,
+    ];
+
+    pub fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// A physical button (or stick direction) that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(KeyboardKey),
+    GamepadButton(GamepadButton),
+    /// Stick axis pushed past half way, in the direction of the sign
+    GamepadAxis(GamepadAxis, f32),
+}
+
+impl Binding {
+    pub fn is_down(&self, rl: &RaylibHandle) -> bool {
+        match *self {
+            Binding::Key(key) => rl.is_key_down(key),
+            Binding::GamepadButton(button) => {
+                rl.is_gamepad_available(0) && rl.is_gamepad_button_down(0, button)
+            }
+            Binding::GamepadAxis(axis, direction) => {
+                rl.is_gamepad_available(0)
+                    && rl.get_gamepad_axis_movement(0, axis) * direction.signum() > 0.5
+            }
+        }
+    }
+
+    /// Went down since the last frame, even if it was let go again already
+    pub fn is_pressed(&self, rl: &RaylibHandle) -> bool {
+        match *self {
+            Binding::Key(key) => rl.is_key_pressed(key),
+            Binding::GamepadButton(button) => {
+                rl.is_gamepad_available(0) && rl.is_gamepad_button_pressed(0, button)
+            }
+            // Sticks have no edges of their own, comparing with the last frame has to do
+            Binding::GamepadAxis(..) => false,
+        }
+    }
+
+    /// Went up since the last frame
+    pub fn is_released(&self, rl: &RaylibHandle) -> bool {
+        match *self {
+            Binding::Key(key) => rl.is_key_released(key),
+            Binding::GamepadButton(button) => {
+                rl.is_gamepad_available(0) && rl.is_gamepad_button_released(0, button)
+            }
+            Binding::GamepadAxis(..) => false,
+        }
+    }
+}
+
+/// Which bindings trigger which actions
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use GamepadAxis::*;
+        use GamepadButton::*;
+        use KeyboardKey::*;
+
+        let mut bindings = Self {
+            bindings: HashMap::new(),
+        };
+        for (action, binding) in [
+            (Action::Left, Binding::Key(KEY_A)),
+            (Action::Left, Binding::Key(KEY_LEFT)),
+            (
+                Action::Left,
+                Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_FACE_LEFT),
+            ),
+            (
+                Action::Left,
+                Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_X, -1.0),
+            ),
+            (Action::Right, Binding::Key(KEY_D)),
+            (Action::Right, Binding::Key(KEY_RIGHT)),
+            (
+                Action::Right,
+                Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_FACE_RIGHT),
+            ),
+            (
+                Action::Right,
+                Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_X, 1.0),
+            ),
+            (Action::Jump, Binding::Key(KEY_SPACE)),
+            (
+                Action::Jump,
+                Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_FACE_DOWN),
+            ),
+            (Action::Grab, Binding::Key(KEY_S)),
+            (Action::Grab, Binding::Key(KEY_DOWN)),
+            (
+                Action::Grab,
+                Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_FACE_DOWN),
+            ),
+            (
+                Action::Grab,
+                Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_TRIGGER_1),
+            ),
+            (Action::Pause, Binding::Key(KEY_P)),
+            (
+                Action::Pause,
+                Binding::GamepadButton(GAMEPAD_BUTTON_MIDDLE_RIGHT),
+            ),
+            (Action::Restart, Binding::Key(KEY_R)),
+            (
+                Action::Restart,
+                Binding::GamepadButton(GAMEPAD_BUTTON_MIDDLE_LEFT),
+            ),
+            (Action::Up, Binding::Key(KEY_W)),
+            (Action::Up, Binding::Key(KEY_UP)),
+            (
+                Action::Up,
+                Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_FACE_UP),
+            ),
+            (Action::Up, Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_Y, -1.0)),
+            (Action::Down, Binding::Key(KEY_S)),
+            (Action::Down, Binding::Key(KEY_DOWN)),
+            (
+                Action::Down,
+                Binding::GamepadButton(GAMEPAD_BUTTON_LEFT_FACE_DOWN),
+            ),
+            (Action::Down, Binding::GamepadAxis(GAMEPAD_AXIS_LEFT_Y, 1.0)),
+            (Action::Confirm, Binding::Key(KEY_ENTER)),
+            (Action::Confirm, Binding::Key(KEY_SPACE)),
+            (
+                Action::Confirm,
+                Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_FACE_DOWN),
+            ),
+            (Action::Back, Binding::Key(KEY_BACKSPACE)),
+            (
+                Action::Back,
+                Binding::GamepadButton(GAMEPAD_BUTTON_RIGHT_FACE_RIGHT),
+            ),
+        ] {
+            bindings.bind(action, binding);
+        }
+        bindings
+    }
+}
+
+impl Bindings {
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Actions with any binding for which `check` holds, as a mask
+    fn mask(&self, rl: &RaylibHandle, check: fn(&Binding, &RaylibHandle) -> bool) -> u16 {
+        Action::ALL
+            .into_iter()
+            .filter(|action| self.get(*action).iter().any(|binding| check(binding, rl)))
+            .fold(0, |mask, action| mask | action.mask())
+    }
+}
+
+/// Snapshot of the actions for a single simulation step
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Input {
+    down: u16,
+    pressed: u16,
+    released: u16,
+}
+
+impl Input {
+    pub fn poll(rl: &RaylibHandle, bindings: &Bindings, previous: &Input) -> Self {
+        let mut input = previous.next(bindings.mask(rl, Binding::is_down));
+        // A tap that went down and up within one frame only shows up in raylib's edges
+        input.pressed |= bindings.mask(rl, Binding::is_pressed);
+        input.released |= bindings.mask(rl, Binding::is_released);
+        input
+    }
+
+    /// Input with the actions in `down` held, pressed and released relative to `self`
+    pub fn next(&self, down: u16) -> Self {
+        Self {
+            down,
+            pressed: down & !self.down,
+            released: !down & self.down,
+        }
+    }
+
+    pub fn down(&self, action: Action) -> bool {
+        self.down & action.mask() != 0
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed & action.mask() != 0
+    }
+
+    pub fn released(&self, action: Action) -> bool {
+        self.released & action.mask() != 0
+    }
+
+    pub fn joy(&self) -> i8 {
+        self.down(Action::Right) as i8 - self.down(Action::Left) as i8
+    }
+
+    /// Take held actions from `other` and keep edges from both
+    pub fn latch(&mut self, other: &Self) {
+        self.down = other.down;
+        self.pressed |= other.pressed;
+        self.released |= other.released;
+    }
+
+    /// Tap `action` without holding it, for actions triggered outside the game (e.g. by a menu)
+    pub fn press(&mut self, action: Action) {
+        self.pressed |= action.mask();
+    }
+
+    pub fn clear_edges(&mut self) {
+        self.pressed = 0;
+        self.released = 0;
+    }
+
+    /// Only the game actions are kept, menus never reach the simulation
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let game = Action::GAME
+            .iter()
+            .fold(0, |mask, action| mask | action.mask());
+        [self.down, self.pressed, self.released].map(|mask| (mask & game) as u8)
+    }
+
+    pub fn from_bytes(bytes: [u8; 3]) -> Self {
+        Self {
+            down: bytes[0].into(),
+            pressed: bytes[1].into(),
+            released: bytes[2].into(),
+        }
+    }
+}
@@ -1,51 +1,113 @@
+use std::rc::Rc;
+
 use ldtk_easy::entity::Field;
 use ldtk_easy::layer::Autotile;
 use raylib::misc::get_random_value;
 
 use crate::assets::*;
+use crate::expression::{self, Token};
+use crate::physics::PhysicsProfile;
 use crate::player::Player;
-
+use crate::sequence::{Sequence, SequenceRule, SEQUENCE_FIELD};
+use crate::tile::{Cell, Direction, Tile, EMPTY, LEVEL_LAYER, WALL};
+
+/// Reach of the walls around the level
+const FAR: f32 = 1000.0;
+/// Overlaps smaller than this are rounding errors of things touching
+const SKIN: f32 = 0.001;
+/// Distance between the digits of numbers without a sprite of their own
+const DIGIT_WIDTH: f32 = 10.0;
+/// Seconds until a spider is back where it started
+pub const SPIDER_CYCLE: f32 = 18.0;
+/// Sprites in `Numbers.png` after the ones for 1 to 10
+const ZERO_GLYPH: i32 = 10;
+const MINUS_GLYPH: i32 = 11;
+const PLUS_GLYPH: i32 = 12;
+const TIMES_GLYPH: i32 = 13;
+const DIVIDE_GLYPH: i32 = 14;
+
+/// Countdowns a level can have by their LDtk enum value, with the tint of their numbers and door
+pub const TRACKS: [(&str, Color); 5] = [
+    ("White", Color::WHITE),
+    (
+        "Red",
+        Color {
+            r: 255,
+            g: 128,
+            b: 128,
+            a: 255,
+        },
+    ),
+    (
+        "Blue",
+        Color {
+            r: 128,
+            g: 176,
+            b: 255,
+            a: 255,
+        },
+    ),
+    (
+        "Green",
+        Color {
+            r: 128,
+            g: 255,
+            b: 128,
+            a: 255,
+        },
+    ),
+    (
+        "Yellow",
+        Color {
+            r: 255,
+            g: 240,
+            b: 112,
+            a: 255,
+        },
+    ),
+];
+
+#[derive(Clone)]
 pub struct Level {
     index: usize,
     size: Vector2,
-    grid: Vec<Tile>,
-    background: Vec<Autotile>,
+    /// Shared between copies, like the background it never changes after loading
+    grid: Rc<[Cell]>,
+    background: Rc<[Autotile]>,
     pub numbers: Vec<Number>,
     web: Vec<Vector2>,
-    pub current_number: u8,
-    door: Rectangle,
-    target_door_y: f32,
+    pub tracks: Vec<Track>,
+    pub physics: PhysicsProfile,
 
     pub particles: Vec<Particle>,
     pub overlays: Vec<Overlay>,
 }
 
 impl Level {
-    pub fn load(assets: &Assets, index: usize) -> Result<Option<(Self, Player)>> {
-        if let Some(level) = assets.world.levels().get(index).cloned() {
-            let grid = level
-                .get_layer("Level")
-                .context("No level map found in level!")?
+    pub fn load(levels: &Levels, index: usize) -> Result<Option<(Self, Player)>> {
+        if let Some(level) = levels.project.levels().get(index).cloned() {
+            let layer = level
+                .get_layer(LEVEL_LAYER)
+                .context("No level map found in level!")?;
+            let tiles = layer
                 .int_grid()
                 .iter()
-                .map(|tile| match tile {
-                    0 => Tile::Empty,
-                    1 => Tile::Ground,
-                    2 => Tile::Spike,
-                    3 => Tile::Spike,
-                    _ => panic!("Undefined tile '{}'!", tile),
+                .map(|&value| {
+                    levels
+                        .tiles
+                        .get(value)
+                        .with_context(|| format!("Undefined tile '{}'!", value))
                 })
-                .collect();
+                .collect::<Result<Vec<_>>>()?;
+            let grid = grid(&tiles, level.pixel_size().0 as usize / 16);
 
-            let background = level
-                .get_layer("Level")
-                .context("No autotile level map found in level!")?
-                .autotiles();
+            let background = layer.autotiles().into();
 
             let mut numbers = Vec::new();
             let mut web = Vec::new();
             let mut player = Player::new(Vector2::default(), Vector2::default());
-            let mut door = Rectangle::default();
+            // Track, where it is and its own target number and rule
+            let mut doors: Vec<(usize, Rectangle, Option<i32>, Option<SequenceRule>)> = Vec::new();
 
             for entity in &level
                 .get_layer("Entities")
@@ -58,28 +120,44 @@ impl Level {
                         rvec2(entity.width(), entity.height()),
                     );
                 } else if entity.identifier() == "Number" {
-                    let value = match entity
-                        .field("Number")
-                        .context("Number entity has no number field!")?
+                    let value = Number::value(
+                        entity
+                            .field("Number")
+                            .context("Number entity has no number field!")?,
+                    )?;
+                    numbers.push(Number::new(
+                        tuple2(entity.pixel_coordinates()),
+                        value,
+                        glyphs(value),
+                        track(entity.field("Track"))?,
+                        None,
+                    ));
+                } else if entity.identifier() == "Expression" {
+                    let text = match entity
+                        .field("Expression")
+                        .context("Expression entity has no expression field!")?
                     {
                         Field::String { value } => value,
-                        _ => bail!("Entity number field is of unexpected type!"),
+                        _ => bail!("Entity expression field is of unexpected type!"),
                     };
+                    let tokens = expression::parse(&text)?;
                     numbers.push(Number::new(
                         tuple2(entity.pixel_coordinates()),
-                        value[6..]
-                            .parse()
-                            .context(format!("Failed to parse number type '{}'!", value))?,
+                        expression::evaluate(&tokens)
+                            .with_context(|| format!("Failed to evaluate '{}'!", text))?,
+                        tokens
+                            .iter()
+                            .flat_map(|token| token_glyphs(*token))
+                            .collect(),
+                        track(entity.field("Track"))?,
                         None,
                     ));
                 } else if entity.identifier() == "Spider" {
-                    let value = match entity
-                        .field("Number")
-                        .context("Spider entity has no number field!")?
-                    {
-                        Field::String { value } => value,
-                        _ => bail!("Entity number field is of unexpected type!"),
-                    };
+                    let value = Number::value(
+                        entity
+                            .field("Number")
+                            .context("Spider entity has no number field!")?,
+                    )?;
                     let target = match entity
                         .field("Target")
                         .context("Spider entity has no target field!")?
@@ -94,23 +172,72 @@ impl Level {
                     };
                     numbers.push(Number::new(
                         tuple2(entity.pixel_coordinates()),
-                        value[6..]
-                            .parse()
-                            .context(format!("Failed to parse number type '{}'!", value))?,
+                        value,
+                        glyphs(value),
+                        track(entity.field("Track"))?,
                         Some(target),
                     ));
                 } else if entity.identifier() == "Door" {
-                    door = rrect(
+                    let kind = track(entity.field("Track"))?;
+                    if doors.iter().any(|(other, ..)| *other == kind) {
+                        bail!("More than one door for the {} countdown!", TRACKS[kind].0);
+                    }
+                    let door = rrect(
                         entity.pixel_coordinates().0,
                         entity.pixel_coordinates().1,
                         entity.width(),
                         entity.height(),
                     );
+                    let target = match entity.field("TargetNumber") {
+                        None | Some(Field::Null) => None,
+                        Some(Field::Int { value }) => Some(value as _),
+                        _ => bail!("Door target number field is of unexpected type!"),
+                    };
+                    doors.push((
+                        kind,
+                        door,
+                        target,
+                        SequenceRule::from_field(entity.field("Sequence"))?,
+                    ));
                 } else if entity.identifier() == "Web" {
                     web.push(tuple2(entity.pixel_coordinates()));
                 }
             }
 
+            let target = match level
+                .field("TargetNumber")
+                .context("Level has no target number!")?
+            {
+                Field::Int { value } => value as _,
+                _ => bail!("Target number field is of unexpected type!"),
+            };
+            let rule = SequenceRule::from_field(level.field(SEQUENCE_FIELD))?
+                .unwrap_or(SequenceRule::CountDown(1));
+            // Doors count down like the level unless they have their own countdown
+            let tracks = doors
+                .into_iter()
+                .map(|(kind, door, door_target, door_rule)| {
+                    Ok(Track {
+                        kind,
+                        sequence: Sequence::new(
+                            door_rule.unwrap_or_else(|| rule.clone()),
+                            door_target.unwrap_or(target),
+                        )?,
+                        door,
+                        target_door_y: door.y - 32.0,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // Numbers point at the tracks of the level from now on
+            for number in &mut numbers {
+                number.track = tracks
+                    .iter()
+                    .position(|track| track.kind == number.track)
+                    .with_context(|| {
+                        format!("No door for the {} countdown!", TRACKS[number.track].0)
+                    })?;
+            }
+
             Ok(Some((
                 Self {
                     index,
@@ -119,15 +246,8 @@ impl Level {
                     background,
                     numbers,
                     web,
-                    current_number: match level
-                        .field("TargetNumber")
-                        .context("Level has no target number!")?
-                    {
-                        Field::Int { value } => value as _,
-                        _ => bail!("Target number field is of unexpected type!"),
-                    },
-                    door,
-                    target_door_y: door.y - 32.0,
+                    tracks,
+                    physics: levels.physics.with_overrides(&level)?,
 
                     particles: Vec::new(),
                     overlays: Vec::new(),
@@ -139,28 +259,62 @@ impl Level {
         }
     }
 
-    pub fn update(&mut self, rl: &mut RaylibHandle) {
-        if self.current_number == 0 {
-            self.door.y = (self.door.y - rl.get_frame_time() * 16.0).max(self.target_door_y);
+    /// Level without an LDtk project where `#` in `rows` is a wall and anything else is empty
+    #[cfg(test)]
+    pub fn from_rows(rows: &[&str], physics: PhysicsProfile) -> Self {
+        let tiles: Vec<&'static Tile> = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|tile| if tile == '#' { &WALL } else { &EMPTY })
+            .collect();
+        Self {
+            index: 0,
+            size: rvec2(rows[0].len() as f32 * 16.0, rows.len() as f32 * 16.0),
+            grid: grid(&tiles, rows[0].len()),
+            background: Vec::new().into(),
+            numbers: Vec::new(),
+            web: Vec::new(),
+            tracks: Vec::new(),
+            physics,
+
+            particles: Vec::new(),
+            overlays: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for track in &mut self.tracks {
+            if track.sequence.finished() {
+                track.door.y = (track.door.y - dt * 16.0).max(track.target_door_y);
+            }
         }
 
         for number in &mut self.numbers {
-            number.update(rl);
+            number.update(dt);
         }
 
         for particle in &mut self.particles {
-            particle.update(rl);
+            particle.update(dt);
         }
         self.particles.retain(Particle::alive);
 
         for overlay in &mut self.overlays {
-            overlay.time -= rl.get_frame_time();
+            overlay.time -= dt;
         }
         self.overlays.retain(|overlay| overlay.time >= 0.0);
     }
 
-    pub fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D) {
-        for tile in &self.background {
+    pub fn save_positions(&mut self) {
+        for number in &mut self.numbers {
+            number.previous_position = number.position;
+        }
+        for particle in &mut self.particles {
+            particle.previous_position = particle.position;
+        }
+    }
+
+    pub fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D, alpha: f32) {
+        for tile in self.background.iter() {
             d.draw_texture_rec(
                 &assets.tileset,
                 rrect(tile.source.0, tile.source.1, 16, 16),
@@ -171,14 +325,16 @@ impl Level {
         for web in &self.web {
             d.draw_texture_v(&assets.web, web, Color::WHITE)
         }
-        d.draw_texture(
-            &assets.door,
-            self.door.x as _,
-            self.door.y as _,
-            Color::WHITE,
-        );
+        for track in &self.tracks {
+            d.draw_texture(
+                &assets.door,
+                track.door.x as _,
+                track.door.y as _,
+                track.color(),
+            );
+        }
         for number in &self.numbers {
-            number.draw(assets, d);
+            number.draw(assets, d, alpha, self.tracks[number.track].color());
         }
     }
 
@@ -190,38 +346,193 @@ impl Level {
         self.size
     }
 
-    pub fn tile(&self, position: Vector2) -> Tile {
+    pub fn tile(&self, position: Vector2) -> &'static Tile {
+        self.cell(position).tile
+    }
+
+    pub fn cell(&self, position: Vector2) -> Cell {
         let size = self.size / 16.0;
         if position.x < 0.0 || position.y < 0.0 || position.x >= size.x || position.y >= size.y {
-            return Tile::Empty;
+            return Cell::new(&EMPTY, |_| false);
         }
         self.grid[position.x as usize + position.y as usize * size.x as usize]
     }
 
-    pub fn door(&self) -> &Rectangle {
-        &self.door
+    /// Solid things around `area`: tiles, the doors and the level's edges, except the exit on the right
+    fn obstacles(&self, area: Rectangle) -> impl Iterator<Item = (Rectangle, &'static Tile)> + '_ {
+        let edges = [
+            rrect(-FAR, -FAR, FAR, self.size.y + FAR * 2.0),
+            rrect(-FAR, -FAR, self.size.x + FAR * 2.0, FAR),
+            rrect(-FAR, self.size.y, self.size.x + FAR * 2.0, FAR),
+        ];
+        let cells = |start: f32, length: f32| {
+            (start / 16.0).floor() as i32..=((start + length) / 16.0).floor() as i32
+        };
+        let columns = cells(area.x, area.width);
+        let tiles = cells(area.y, area.height)
+            .flat_map(move |y| columns.clone().map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let tile = self.tile(rvec2(x, y));
+                tile.solid.then(|| (rrect(x * 16, y * 16, 16, 16), tile))
+            });
+        self.tracks
+            .iter()
+            .map(|track| track.door)
+            .chain(edges)
+            .map(|obstacle| (obstacle, &WALL))
+            .chain(tiles)
+    }
+
+    /// Something solid `rect` is inside of
+    pub fn overlapping(&self, rect: Rectangle) -> Option<Rectangle> {
+        self.obstacles(rect)
+            .map(|(obstacle, _)| obstacle)
+            .find(|obstacle| {
+                overlap(span(rect, true), span(*obstacle, true))
+                    && overlap(span(rect, false), span(*obstacle, false))
+            })
+    }
+
+    /// Moves `rect` by `motion`, which is along a single axis, until it touches something solid.
+    /// Returns how far it got and what it ran into.
+    pub fn sweep(&self, rect: Rectangle, motion: Vector2) -> (Vector2, Option<&'static Tile>) {
+        let length = motion.x.abs() + motion.y.abs();
+        if length == 0.0 {
+            return (Vector2::zero(), None);
+        }
+        // Everything the rect passes through on the way, so nothing can be skipped
+        let area = rrect(
+            rect.x + motion.x.min(0.0),
+            rect.y + motion.y.min(0.0),
+            rect.width + motion.x.abs(),
+            rect.height + motion.y.abs(),
+        );
+        let horizontal = motion.x != 0.0;
+        let forward = motion.x + motion.y > 0.0;
+        let (start, end) = span(rect, horizontal);
+
+        let mut distance = length;
+        let mut hit = None;
+        for (obstacle, tile) in self.obstacles(area) {
+            if !overlap(span(rect, !horizontal), span(obstacle, !horizontal)) {
+                continue;
+            }
+            let (near, far) = span(obstacle, horizontal);
+            let (gap, ahead) = if forward {
+                (near - end, far > end)
+            } else {
+                (start - far, near < start)
+            };
+            if ahead && gap < distance {
+                distance = gap.max(0.0);
+                hit = Some(tile);
+            }
+        }
+        (motion / length * distance, hit)
+    }
+}
+
+/// Cells of a level `width` tiles wide, from its tiles row by row
+fn grid(tiles: &[&'static Tile], width: usize) -> Rc<[Cell]> {
+    (0..tiles.len())
+        .map(|index| {
+            Cell::new(tiles[index], |direction| {
+                let (x, y) = (index % width, index / width);
+                let neighbour = match direction {
+                    Direction::Up => y.checked_sub(1).map(|y| (x, y)),
+                    Direction::Down => Some((x, y + 1)),
+                    Direction::Left => x.checked_sub(1).map(|x| (x, y)),
+                    Direction::Right => Some((x + 1, y)).filter(|(x, _)| *x < width),
+                };
+                neighbour
+                    .and_then(|(x, y)| tiles.get(x + y * width))
+                    .is_some_and(|tile| tile.solid)
+            })
+        })
+        .collect()
+}
+
+/// A countdown of the level, with the door it opens
+#[derive(Clone)]
+pub struct Track {
+    /// Index into [`TRACKS`]
+    kind: usize,
+    pub sequence: Sequence,
+    door: Rectangle,
+    target_door_y: f32,
+}
+
+impl Track {
+    pub fn color(&self) -> Color {
+        TRACKS[self.kind].1
+    }
+
+    pub fn door(&self) -> Rectangle {
+        self.door
+    }
+
+    /// Seconds until the door is fully open, once it started opening
+    pub fn door_time_left(&self) -> f32 {
+        (self.door.y - self.target_door_y) / 16.0
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Tile {
-    Empty,
-    Ground,
-    Spike,
+/// Index into [`TRACKS`] of a Track field, numbers and doors without one are White
+fn track(field: Option<Field>) -> Result<usize> {
+    match field {
+        None | Some(Field::Null) => Ok(0),
+        Some(Field::String { value }) => TRACKS
+            .iter()
+            .position(|(name, _)| *name == value)
+            .with_context(|| format!("Unknown countdown track '{}'!", value)),
+        _ => bail!("Entity track field is of unexpected type!"),
+    }
 }
 
+/// Start and end of `rect` along the x axis, or the y axis
+fn span(rect: Rectangle, horizontal: bool) -> (f32, f32) {
+    if horizontal {
+        (rect.x, rect.x + rect.width)
+    } else {
+        (rect.y, rect.y + rect.height)
+    }
+}
+
+/// Whether two spans overlap by more than rounding errors, just touching doesn't count
+fn overlap(a: (f32, f32), b: (f32, f32)) -> bool {
+    a.0 < b.1 - SKIN && b.0 < a.1 - SKIN
+}
+
+#[derive(Clone)]
 pub struct Number {
     position: Vector2,
-    number: u8,
+    previous_position: Vector2,
+    number: i32,
+    /// Index into the level's tracks
+    track: usize,
+    /// Sprites it's drawn with, shared between copies
+    glyphs: Rc<[i32]>,
+    /// Numbers with more digits are wider, around the same center
+    width: f32,
     timer: f32,
     spider: Option<Spider>,
 }
 
 impl Number {
-    fn new(position: Vector2, number: u8, spider: Option<Vector2>) -> Self {
+    fn new(
+        position: Vector2,
+        number: i32,
+        glyphs: Vec<i32>,
+        track: usize,
+        spider: Option<Vector2>,
+    ) -> Self {
         Self {
             position,
+            previous_position: position,
             number,
+            track,
+            width: 16.0 + glyphs.len().saturating_sub(1) as f32 * DIGIT_WIDTH,
+            glyphs: glyphs.into(),
             timer: get_random_value::<i32>(0, 120) as f32 / 180.0 * std::f32::consts::PI,
             spider: spider.map(|spider| Spider {
                 origin: position,
@@ -231,9 +542,9 @@ impl Number {
         }
     }
 
-    fn update(&mut self, rl: &mut RaylibHandle) {
+    fn update(&mut self, dt: f32) {
         if let Some(spider) = &mut self.spider {
-            spider.timer = (spider.timer + rl.get_frame_time()) % 18.0;
+            spider.timer = (spider.timer + dt) % SPIDER_CYCLE;
             match (spider.timer / 3.0) as i32 {
                 0 => self.position = spider.origin.lerp(spider.target, spider.timer / 3.0),
                 2 => {
@@ -244,52 +555,129 @@ impl Number {
                 _ => (),
             }
         } else {
-            self.timer += rl.get_frame_time();
+            self.timer += dt;
         }
     }
 
-    fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D) {
+    fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D, alpha: f32, tint: Color) {
+        let position = self.previous_position.lerp(self.position, alpha);
         let position = if let Some(spider) = &self.spider {
-            d.draw_line_ex(
-                spider.origin + 12.0,
-                self.position + 12.0,
-                2.0,
-                Color::WHITE,
-            );
-            d.draw_texture_v(&assets.spider, self.position, Color::WHITE);
-            self.position + 4.0
+            d.draw_line_ex(spider.origin + 12.0, position + 12.0, 2.0, Color::WHITE);
+            d.draw_texture_v(&assets.spider, position, Color::WHITE);
+            position + 4.0
         } else {
-            self.position + rvec2(0, (self.timer * 3.0).sin() * 8.0)
+            position + rvec2(0, (self.timer * 3.0).sin() * 8.0)
         };
-        d.draw_texture_rec(
-            &assets.numbers,
-            rrect((self.number - 1) * 16, 0, 16, 16),
-            position,
-            Color::WHITE,
-        );
+        let left = position.x + 8.0 - self.width / 2.0;
+        for (index, glyph) in self.glyphs.iter().enumerate() {
+            d.draw_texture_rec(
+                &assets.numbers,
+                rrect(*glyph * 16, 0, 16, 16),
+                rvec2(left + index as f32 * DIGIT_WIDTH, position.y),
+                tint,
+            );
+        }
     }
 
     pub fn rect(&self) -> Rectangle {
-        rrect(self.position.x, self.position.y, 16, 16)
+        rrect(
+            self.position.x + 8.0 - self.width / 2.0,
+            self.position.y,
+            self.width,
+            16,
+        )
     }
 
     pub fn center(&self) -> Vector2 {
         self.position + 8.0
     }
 
-    pub fn number(&self) -> u8 {
+    pub fn number(&self) -> i32 {
         self.number
     }
+
+    pub fn track(&self) -> usize {
+        self.track
+    }
+
+    /// Reads a Number field, an enum value like `Number12` or `NumberMinus3`, or just the number
+    pub fn parse(text: &str) -> Result<i32> {
+        let digits = text.strip_prefix("Number").unwrap_or(text);
+        let (sign, digits) = match digits.strip_prefix("Minus") {
+            Some(digits) => (-1, digits),
+            None => (1, digits),
+        };
+        // Only digits, so signs of their own like in 'Number-3' don't sneak through
+        Some(digits)
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<i64>().ok())
+            .and_then(|number| i32::try_from(number * sign).ok())
+            .with_context(|| format!("Failed to parse number type '{}'!", text))
+    }
+
+    fn value(field: Field) -> Result<i32> {
+        match field {
+            Field::Int { value } => Ok(value as _),
+            Field::String { value } => Self::parse(&value),
+            _ => bail!("Entity number field is of unexpected type!"),
+        }
+    }
+
+    pub fn is_spider(&self) -> bool {
+        self.spider.is_some()
+    }
+
+    /// Ends of the line a spider moves along, a plain number stays in place
+    pub fn path(&self) -> (Vector2, Vector2) {
+        match &self.spider {
+            Some(spider) => (spider.origin, spider.target),
+            None => (self.position, self.position),
+        }
+    }
+}
+
+/// Sprites in `Numbers.png` a number is drawn with, from left to right.
+/// 1 to 10 have their own, others are put together from digits.
+fn glyphs(number: i32) -> Vec<i32> {
+    if (1..=10).contains(&number) {
+        return vec![number - 1];
+    }
+    (number < 0)
+        .then_some(MINUS_GLYPH)
+        .into_iter()
+        .chain(token_glyphs(Token::Number(number.unsigned_abs())))
+        .collect()
 }
 
+/// Sprites of a part of an expression
+fn token_glyphs(token: Token) -> Vec<i32> {
+    match token {
+        Token::Number(number) => number
+            .to_string()
+            .bytes()
+            .map(|digit| match digit - b'0' {
+                0 => ZERO_GLYPH,
+                digit => digit as i32 - 1,
+            })
+            .collect(),
+        Token::Add => vec![PLUS_GLYPH],
+        Token::Subtract => vec![MINUS_GLYPH],
+        Token::Multiply => vec![TIMES_GLYPH],
+        Token::Divide => vec![DIVIDE_GLYPH],
+    }
+}
+
+#[derive(Clone)]
 pub struct Spider {
     origin: Vector2,
     target: Vector2,
     timer: f32,
 }
 
+#[derive(Clone)]
 pub struct Particle {
     position: Vector2,
+    previous_position: Vector2,
     velocity: Vector2,
     life_time: f32,
     color: Color,
@@ -299,20 +687,24 @@ impl Particle {
     pub fn new(position: Vector2, velocity: Vector2, life_time: f32, color: Color) -> Self {
         Self {
             position,
+            previous_position: position,
             velocity,
             life_time,
             color,
         }
     }
 
-    pub fn update(&mut self, rl: &mut RaylibHandle) {
-        self.life_time -= rl.get_frame_time();
-        self.position += self.velocity * rl.get_frame_time();
-        self.velocity.y += 1000.0 * rl.get_frame_time();
+    pub fn update(&mut self, dt: f32) {
+        self.life_time -= dt;
+        self.position += self.velocity * dt;
+        self.velocity.y += 1000.0 * dt;
     }
 
-    pub fn draw<D: RaylibDraw>(&self, d: &mut D) {
-        d.draw_pixel_v(self.position, self.color);
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D, alpha: f32) {
+        d.draw_pixel_v(
+            self.previous_position.lerp(self.position, alpha),
+            self.color,
+        );
     }
 
     pub fn alive(&self) -> bool {
@@ -320,6 +712,7 @@ impl Particle {
     }
 }
 
+#[derive(Clone)]
 pub struct Overlay {
     text: String,
     pub time: f32,
@@ -334,3 +727,278 @@ impl Overlay {
         &self.text
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::input::Input;
+    use crate::physics::PhysicsProfile;
+    use crate::world::{Event, TICK};
+
+    /// A wall one tile thin in the middle, with a block on the floor to its left
+    const ROWS: [&str; 6] = [
+        "........", "....#...", "....#...", "....#...", "..#.#...", "########",
+    ];
+
+    fn level() -> Level {
+        Level::from_rows(&ROWS, PhysicsProfile::default())
+    }
+
+    fn assert_close(a: Vector2, b: Vector2) {
+        assert!(a.distance_to(b) < 0.01, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn obstacles_are_solid_tiles_doors_and_edges() {
+        let mut level = level();
+        let door = rrect(112, 16, 16, 32);
+        level.tracks.push(Track {
+            kind: 0,
+            sequence: Sequence::new(SequenceRule::CountDown(1), 1).unwrap(),
+            door,
+            target_door_y: door.y - 32.0,
+        });
+        let obstacles: Vec<_> = level
+            .obstacles(rrect(56, 8, 16, 16))
+            .map(|(obstacle, _)| obstacle)
+            .collect();
+        // The door, the left, top and bottom edges, and the wall tile the area reaches into
+        assert_eq!(obstacles.len(), 5);
+        assert!(obstacles.contains(&door));
+        assert!(obstacles.contains(&rrect(64, 16, 16, 16)));
+        assert!(!obstacles.contains(&rrect(48, 16, 16, 16)));
+        // Nothing stops the player leaving on the right
+        assert!(obstacles.iter().all(|obstacle| obstacle.x < level.size().x));
+    }
+
+    #[test]
+    fn touching_is_not_overlapping() {
+        let level = level();
+        let wall = rrect(64, 16, 16, 16);
+        assert_eq!(level.overlapping(rrect(48, 16, 16, 16)), None);
+        assert_eq!(level.overlapping(rrect(48.5, 16, 16, 16)), Some(wall));
+        assert_eq!(level.overlapping(rrect(0, 64, 16, 16)), None);
+        assert!(level.overlapping(rrect(-0.5, 0, 16, 16)).is_some());
+        assert!(level.overlapping(rrect(0, 80.5, 16, 16)).is_some());
+        assert_eq!(level.overlapping(rrect(120.5, 0, 16, 16)), None);
+    }
+
+    #[test]
+    fn sweeps_stop_at_the_first_thing_in_the_way() {
+        let level = level();
+        let rect = rrect(20, 20, 16, 16);
+        let (moved, hit) = level.sweep(rect, Vector2::zero());
+        assert_eq!(moved, Vector2::zero());
+        assert!(hit.is_none());
+        // However fast it goes, it stops in front of the thin wall, the block and the edges
+        let (moved, hit) = level.sweep(rect, rvec2(FAR * 10.0, 0));
+        assert_close(moved, rvec2(28, 0));
+        assert!(hit.is_some_and(|tile| tile.solid));
+        assert_close(level.sweep(rect, rvec2(-FAR * 10.0, 0)).0, rvec2(-20, 0));
+        assert_close(level.sweep(rect, rvec2(0, -FAR * 10.0)).0, rvec2(0, -20));
+        assert_close(level.sweep(rect, rvec2(0, FAR * 10.0)).0, rvec2(0, 28));
+        // Except on the right, where the level is left
+        let beyond = rrect(84, 0, 16, 16);
+        let (moved, hit) = level.sweep(beyond, rvec2(FAR, 0));
+        assert_eq!(moved, rvec2(FAR, 0));
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn sweeps_slide_past_corners() {
+        let level = level();
+        // Level with the top of the block on the floor, and just over it
+        let above = rrect(8, 48, 16, 16);
+        assert_close(level.sweep(above, rvec2(100, 0)).0, rvec2(40, 0));
+        let (moved, hit) = level.sweep(rrect(8, 48.5, 16, 16), rvec2(100, 0));
+        assert_close(moved, rvec2(8, 0));
+        assert!(hit.is_some());
+        // Right next to the block, falling past its side onto the floor
+        let beside = rrect(16, 0, 16, 16);
+        assert_close(level.sweep(beside, rvec2(0, 100)).0, rvec2(0, 64));
+        // Already touching something it moves away from
+        assert_close(level.sweep(above, rvec2(0, -8)).0, rvec2(0, -8));
+    }
+
+    #[test]
+    fn parses_number_names() {
+        assert_eq!(Number::parse("Number12").unwrap(), 12);
+        assert_eq!(Number::parse("NumberMinus3").unwrap(), -3);
+        assert_eq!(Number::parse("Number0").unwrap(), 0);
+        assert_eq!(Number::parse("NumberMinus0").unwrap(), 0);
+        assert_eq!(Number::parse("NumberMinus2147483648").unwrap(), i32::MIN);
+        for name in [
+            "",
+            "Number",
+            "NumberMinus",
+            "NumberTwelve",
+            "Number-3",
+            "Number+3",
+            "NumberMinus-3",
+            "Number 3",
+            "Number3a",
+            "Number2147483648",
+        ] {
+            assert!(Number::parse(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn glyphs_of_numbers() {
+        // 1 to 10 have sprites of their own
+        assert_eq!(glyphs(1), [0]);
+        assert_eq!(glyphs(10), [9]);
+        // Others are put together from digits, where 1 to 9 use the sprites of 1 to 9
+        assert_eq!(glyphs(0), [ZERO_GLYPH]);
+        assert_eq!(glyphs(11), [0, 0]);
+        assert_eq!(glyphs(205), [1, ZERO_GLYPH, 4]);
+        assert_eq!(glyphs(-3), [MINUS_GLYPH, 2]);
+        assert_eq!(glyphs(-10), [MINUS_GLYPH, 0, ZERO_GLYPH]);
+        assert_eq!(glyphs(i32::MIN).len(), 11);
+    }
+
+    /// The first level with `edit` applied to its entities, which get a Red countdown next to
+    /// the White one: a door counting down from 3 and its numbers up in the open air
+    fn two_tracks(edit: impl FnOnce(&mut Vec<Value>)) -> Result<Level> {
+        let mut project: Value = serde_json::from_str(include_str!("../levels.ldtk")).unwrap();
+        let entities = project["levels"][0]["layerInstances"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|layer| layer["__identifier"] == "Entities")
+            .unwrap()["entityInstances"]
+            .as_array_mut()
+            .unwrap();
+        let find = |identifier: &str| {
+            entities
+                .iter()
+                .find(|entity| entity["__identifier"] == identifier)
+                .unwrap()
+                .clone()
+        };
+        let (number, mut door) = (find("Number"), find("Door"));
+        door["px"] = json!([480, 16]);
+        door["fieldInstances"] = json!([
+            { "__identifier": "Track", "__value": "Red" },
+            { "__identifier": "TargetNumber", "__value": 3 },
+        ]);
+        entities.push(door);
+        for value in 1..=3 {
+            let mut number = number.clone();
+            number["px"] = json!([32 + value * 32, 48]);
+            number["fieldInstances"] = json!([
+                { "__identifier": "Number", "__value": format!("Number{}", value) },
+                { "__identifier": "Track", "__value": "Red" },
+            ]);
+            entities.push(number);
+        }
+        edit(entities);
+        let levels = parse_levels(&project.to_string(), Path::new("")).unwrap();
+        Ok(Level::load(&levels, 0)?.unwrap().0)
+    }
+
+    /// Index into the level's tracks of the one with the given name
+    fn track_index(level: &Level, name: &str) -> usize {
+        level
+            .tracks
+            .iter()
+            .position(|track| TRACKS[track.kind].0 == name)
+            .unwrap()
+    }
+
+    /// Drops a player onto number `value` of `track` for a tick
+    fn touch(level: &mut Level, track: usize, value: i32) -> Vec<Event> {
+        let number = level
+            .numbers
+            .iter()
+            .find(|number| number.track() == track && number.number() == value)
+            .unwrap();
+        let mut player = Player::new(number.position, rvec2(16, 24));
+        let mut events = Vec::new();
+        player.update(TICK, &Input::default(), level, &mut events);
+        events
+    }
+
+    /// Lets the doors move for `seconds`
+    fn wait(level: &mut Level, seconds: f32) {
+        for _ in 0..(seconds / TICK) as usize {
+            level.update(TICK);
+        }
+    }
+
+    #[test]
+    fn numbers_only_count_on_their_own_track() {
+        let mut level = two_tracks(|_| ()).unwrap();
+        let (white, red) = (track_index(&level, "White"), track_index(&level, "Red"));
+        assert_eq!(level.tracks.len(), 2);
+        assert_eq!(level.tracks[red].sequence.order(), [3, 2, 1]);
+
+        // Both want a 3 next, taking one only moves its own countdown along
+        assert_eq!(touch(&mut level, white, 3), [Event::Number]);
+        assert_eq!(level.tracks[white].sequence.collected(), 1);
+        assert_eq!(level.tracks[red].sequence.collected(), 0);
+        // White wants a 2 now, but a Red 2 is still a mistake
+        assert_eq!(touch(&mut level, red, 2), [Event::Mistake]);
+        assert_eq!(level.tracks[white].sequence.collected(), 1);
+        assert_eq!(level.tracks[red].sequence.collected(), 0);
+        assert_eq!(touch(&mut level, red, 3), [Event::Number]);
+        assert_eq!(level.tracks[red].sequence.next(), Some(2));
+    }
+
+    #[test]
+    fn doors_open_after_their_own_track() {
+        let mut level = two_tracks(|_| ()).unwrap();
+        let (white, red) = (track_index(&level, "White"), track_index(&level, "Red"));
+        let closed: Vec<_> = level.tracks.iter().map(|track| track.door().y).collect();
+
+        for value in (1..=3).rev() {
+            touch(&mut level, red, value);
+        }
+        assert!(level.tracks[red].sequence.finished());
+        assert!(!level.tracks[white].sequence.finished());
+        wait(&mut level, 3.0);
+        assert_eq!(level.tracks[red].door().y, closed[red] - 32.0);
+        assert_eq!(level.tracks[white].door().y, closed[white]);
+
+        for value in (1..=3).rev() {
+            touch(&mut level, white, value);
+        }
+        wait(&mut level, 1.0);
+        assert!(level.tracks[white].door().y < closed[white]);
+        assert!(level.tracks[white].door_time_left() > 0.0);
+        wait(&mut level, 2.0);
+        assert_eq!(level.tracks[white].door_time_left(), 0.0);
+    }
+
+    #[test]
+    fn every_track_needs_exactly_one_door() {
+        let error = two_tracks(|entities| {
+            let door = entities
+                .iter()
+                .find(|entity| entity["__identifier"] == "Door")
+                .unwrap()
+                .clone();
+            entities.push(door);
+        })
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "More than one door for the White countdown!"
+        );
+
+        let error = two_tracks(|entities| {
+            entities.retain(|entity| {
+                entity["__identifier"] != "Door"
+                    || entity["fieldInstances"].as_array().unwrap().is_empty()
+            })
+        })
+        .err()
+        .unwrap();
+        assert_eq!(error.to_string(), "No door for the Red countdown!");
+    }
+}
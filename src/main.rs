@@ -1,9 +1,41 @@
 #![windows_subsystem = "windows"]
 
+use std::path::{Path, PathBuf};
+
 pub mod assets;
+pub mod expression;
+pub mod forgiveness;
+pub mod ghost;
+pub mod input;
 pub mod level;
+pub mod physics;
 pub mod player;
+pub mod reload;
+pub mod replay;
+pub mod save;
+pub mod scene;
+pub mod scenes;
+pub mod sequence;
+pub mod solver;
+pub mod splits;
+pub mod tile;
+pub mod timing;
+pub mod ui;
+pub mod validate;
+pub mod world;
 use assets::*;
+use forgiveness::Forgiveness;
+use ghost::{GhostMode, Ghosts};
+use input::*;
+use physics::PHYSICS_PATH;
+use reload::FileWatcher;
+use replay::Replay;
+use save::{data_dir, SaveData};
+use scene::{Scene, SceneStack, Shared};
+use scenes::{Playing, Title};
+use splits::Splits;
+use timing::TimingMethod;
+use world::TICK;
 
 fn format_time(time: f32) -> String {
     format!(
@@ -14,299 +46,228 @@ fn format_time(time: f32) -> String {
     )
 }
 
-struct Button {
-    scale: f32,
-    last_hovered: bool,
-    position: Vector2,
+#[derive(Default)]
+struct Args {
+    /// Check the levels and exit instead of starting the game
+    validate: bool,
+    /// Search for a solution to every level and exit
+    solve: bool,
+    replay: Option<String>,
+    levels: Option<String>,
+    ghost: Option<GhostMode>,
+    splits: Option<String>,
+    timing: Option<TimingMethod>,
+    respawn_fade: Option<f32>,
+    forgiveness: Option<Forgiveness>,
 }
 
-impl Button {
-    fn new(position: Vector2) -> Self {
-        Self {
-            scale: 0.0,
-            last_hovered: false,
-            position,
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "validate" => args.validate = true,
+                "solve" => args.solve = true,
+                "--replay" => {
+                    args.replay = Some(iter.next().context("Expected a path after --replay!")?)
+                }
+                "--levels" => {
+                    args.levels = Some(iter.next().context("Expected a path after --levels!")?)
+                }
+                "--splits" => {
+                    args.splits = Some(iter.next().context("Expected a path after --splits!")?)
+                }
+                "--timing" => {
+                    args.timing = Some(
+                        match iter
+                            .next()
+                            .context("Expected a timing method after --timing!")?
+                            .as_str()
+                        {
+                            "rta" => TimingMethod::RealTime,
+                            "igt" => TimingMethod::GameTime,
+                            method => bail!("Unknown timing method '{}'!", method),
+                        },
+                    )
+                }
+                "--respawn-fade" => {
+                    let fade = iter
+                        .next()
+                        .context("Expected a number of seconds after --respawn-fade!")?;
+                    args.respawn_fade = Some(
+                        fade.parse()
+                            .with_context(|| format!("Invalid respawn fade '{}'!", fade))?,
+                    )
+                }
+                "--forgiveness" => {
+                    let mode = iter
+                        .next()
+                        .context("Expected a forgiveness mode after --forgiveness!")?;
+                    args.forgiveness = Some(
+                        Forgiveness::ALL
+                            .into_iter()
+                            .find(|forgiveness| forgiveness.key() == mode)
+                            .with_context(|| format!("Unknown forgiveness mode '{}'!", mode))?,
+                    )
+                }
+                "--ghost" => {
+                    args.ghost = Some(
+                        match iter
+                            .next()
+                            .context("Expected a ghost mode after --ghost!")?
+                            .as_str()
+                        {
+                            "off" => GhostMode::Off,
+                            "level" => GhostMode::Level,
+                            "run" => GhostMode::Run,
+                            mode => bail!("Unknown ghost mode '{}'!", mode),
+                        },
+                    )
+                }
+                _ => bail!("Unknown argument '{}'!", arg),
+            }
         }
+        Ok(args)
     }
+}
 
-    fn update(
-        &mut self,
-        rl: &mut RaylibHandle,
-        asset: &Texture2D,
-        audio: &mut RaylibAudio,
-        hover_sound: &Sound,
-        click_sound: &Sound,
-    ) -> bool {
-        let hovered = self
-            .rect(asset)
-            .check_collision_point_rec(rl.get_mouse_position());
-
-        if hovered && !self.last_hovered {
-            audio.play_sound(hover_sound);
-        }
-
-        self.last_hovered = hovered;
-        self.scale += (if hovered { 1.3 } else { 1.0 } * 3.0 - self.scale)
-            * (1.0 - 0.5_f32.powf(rl.get_frame_time() / 0.3));
-
-        if hovered && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
-            audio.play_sound(click_sound);
-            true
-        } else {
-            false
-        }
+/// Proves every level can be finished, saving the fastest solutions found as replays
+fn solve(levels: &Levels) -> Result<()> {
+    let mut unsolved = 0;
+    for index in 0..levels.project.levels().len() {
+        let Some(solution) = solver::solve(levels, index)? else {
+            println!("Level {}: no solution found", index);
+            unsolved += 1;
+            continue;
+        };
+        let path = data_dir()
+            .join("solutions")
+            .join(format!("level_{}.ctcr", index));
+        solution
+            .replay
+            .save(&path)
+            .context("Failed to save solution!")?;
+        // The search is greedy, a faster way might exist
+        println!(
+            "Level {}: {} ticks ({}, an upper bound), saved to '{}'",
+            index,
+            solution.ticks,
+            format_time(solution.ticks as f32 * TICK),
+            path.display()
+        );
     }
+    ensure!(unsolved == 0, "{} levels could not be solved!", unsolved);
+    Ok(())
+}
 
-    fn tl(&self, asset: &Texture2D) -> Vector2 {
-        self.position - rvec2(asset.width, asset.height) / 2.0 * self.scale
+/// The game runs without a console window on Windows, so the tools print into the one they
+/// were started from (if any)
+#[cfg(windows)]
+fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
     }
-
-    fn rect(&self, asset: &Texture2D) -> Rectangle {
-        let tl = self.tl(asset);
-        rrect(
-            tl.x,
-            tl.y,
-            asset.width as f32 * self.scale,
-            asset.height as f32 * self.scale,
-        )
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
     }
 }
 
+#[cfg(not(windows))]
+fn attach_console() {}
+
 fn main() -> Result<()> {
+    attach_console();
+    let args = Args::parse()?;
+    let levels = args.levels.as_deref().map(Path::new);
+    if args.validate {
+        let issues = validate::validate(&levels_text(levels)?)?;
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        ensure!(
+            issues.is_empty(),
+            "Found {} issues in the levels!",
+            issues.len()
+        );
+        println!("All levels are valid");
+        return Ok(());
+    }
+    if args.solve {
+        return solve(&read_levels(levels)?);
+    }
+    let playback = args.replay.map(Replay::load).transpose()?;
+
     let (mut rl, thread) = raylib::init()
         .size(768, 768)
         .title("Catch the Countdown!")
         .build();
-    let mut assets = Assets::load(&mut rl, &thread).context("Failed to load assets!")?;
-    let mut music = false;
-
-    {
-        let center = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0 - rvec2(0, 80);
-        let mut button_play = Button::new(center);
-        let mut button_music = Button::new(center + rvec2(0, 150));
-
-        loop {
-            if rl.window_should_close() {
-                return Ok(());
-            }
-
-            if button_play.update(
-                &mut rl,
-                &assets.button_play,
-                &mut assets.audio,
-                &assets.button_hover_sound,
-                &assets.button_click_sound,
-            ) {
-                break;
-            }
-
-            if button_music.update(
-                &mut rl,
-                if music {
-                    &assets.button_nomusic
-                } else {
-                    &assets.button_music
-                },
-                &mut assets.audio,
-                &assets.button_hover_sound,
-                &assets.button_click_sound,
-            ) {
-                music = !music;
-            }
-
-            let music_asset = if music {
-                &assets.button_nomusic
-            } else {
-                &assets.button_music
-            };
-
-            let mut d = rl.begin_drawing(&thread);
-            d.draw_texture_ex(
-                &assets.title_screen,
-                Vector2::zero(),
-                0.0,
-                3.0,
-                Color::WHITE,
-            );
-
-            d.draw_texture_ex(
-                &assets.button_play,
-                button_play.tl(&assets.button_play),
-                0.0,
-                button_play.scale,
-                Color::WHITE,
-            );
-
-            d.draw_texture_ex(
-                music_asset,
-                button_music.tl(music_asset),
-                0.0,
-                button_music.scale,
-                Color::WHITE,
-            );
-        }
+    let assets = Assets::load(&mut rl, &thread, levels).context("Failed to load assets!")?;
+    let levels_path = assets.levels_path.clone();
+    let mut watcher = levels_path
+        .as_ref()
+        .map(|path| FileWatcher::new(vec![path.clone(), path.with_file_name(PHYSICS_PATH)]));
+    let mut save = SaveData::load();
+    if let Some(ghost) = args.ghost {
+        save.settings.ghost = ghost;
     }
-
-    let (mut level, mut player) = level::Level::load(&assets, 0)
-        .context("Failed to load level!")?
-        .context("Failed to find first level!")?;
-    let mut state = State::Playing;
-    let mut time = 0.0;
-    loop {
+    if let Some(timing) = args.timing {
+        save.settings.timing = timing;
+    }
+    if let Some(fade) = args.respawn_fade {
+        save.settings.respawn_fade = fade.max(0.0);
+    }
+    if let Some(forgiveness) = args.forgiveness {
+        save.settings.forgiveness = forgiveness;
+    }
+    let best_ghosts = Ghosts::load(assets.world.project.levels().len());
+    let splits_path = args
+        .splits
+        .map_or_else(|| data_dir().join("splits.lss"), PathBuf::from);
+    let splits = Splits::new(assets.world.project.levels().len());
+
+    let mut shared = Shared {
+        assets,
+        save,
+        splits,
+        splits_path,
+        splits_mode: None,
+        best_ghosts,
+        bindings: Bindings::default(),
+        input: Input::default(),
+    };
+    let forgiveness = shared.save.settings.forgiveness;
+    shared.use_splits(forgiveness);
+    let first: Box<dyn Scene> = match playback {
+        Some(replay) => Box::new(Playing::new(&mut shared, 0, Some(replay))?),
+        None => Box::new(Title::new(&rl)),
+    };
+    let mut scenes = SceneStack::new(first);
+
+    while !scenes.is_empty() {
         if rl.window_should_close() {
-            return Ok(());
-        }
-
-        if rl.is_key_pressed(KeyboardKey::KEY_P) {
-            state = match state {
-                State::Playing => State::Paused,
-                State::Paused => State::Playing,
-                state => state,
-            };
+            break;
         }
-
-        if music && !assets.audio.is_sound_playing(&assets.song) {
-            assets.audio.play_sound(&assets.song);
-        }
-
-        if state != State::Paused {
-            time += rl.get_frame_time();
-            level.update(&mut rl);
-        }
-        if state == State::Playing {
-            player.update(&mut assets, &mut rl, &mut level, &mut state);
-            if player.position().x >= level.size().x {
-                state = State::transition(level.index() + 1);
-            }
-        }
-
-        if let State::LevelTransition {
-            next_level,
-            timer,
-            sound_played,
-            loaded,
-        } = &mut state
-        {
-            *timer -= rl.get_frame_time();
-
-            if *timer <= 0.5 && !*sound_played {
-                if *next_level != level.index() {
-                    assets.audio.play_sound(&assets.next_level_sound);
+        if let (Some(watcher), Some(path)) = (&mut watcher, &levels_path) {
+            if watcher.changed(rl.get_frame_time()) {
+                // Keep playing the old levels if the files are broken, e.g. halfway through saving
+                let reloaded = load_levels(path).and_then(|levels| {
+                    scenes.reload(&levels)?;
+                    Ok(levels)
+                });
+                match reloaded {
+                    Err(err) => eprintln!("Failed to reload levels: {:?}", err),
+                    Result::Ok(levels) => shared.swap_levels(levels),
                 }
-                *sound_played = true;
-            } else if *timer <= 0.0 && !*loaded {
-                if let Some((next_level, next_player)) =
-                    level::Level::load(&assets, *next_level).context("Failed to load level!")?
-                {
-                    (level, player) = (next_level, next_player);
-                    *loaded = true;
-                } else {
-                    break;
-                }
-            }
-            if *timer <= -0.5 {
-                state = State::Playing;
             }
         }
+        shared.input = Input::poll(&rl, &shared.bindings, &shared.input);
+        scenes.update(&mut rl, &mut shared)?;
 
-        let center = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0;
         let mut d = rl.begin_drawing(&thread);
-        // d.clear_background(Color::new(86, 86, 86, 255));
-        d.draw_texture_ex(&assets.background, Vector2::zero(), 0.0, 3.0, Color::WHITE);
-        {
-            let mut d = d.begin_mode2D(player.camera(&level));
-            level.draw(&assets, &mut d);
-            if match state {
-                State::Playing => true,
-                State::LevelTransition { loaded, .. } => loaded,
-                State::Paused => true,
-            } {
-                player.draw(&assets, &mut d);
-            }
-            for particle in &level.particles {
-                particle.draw(&mut d);
-            }
-        }
-
-        for overlay in &level.overlays {
-            let font_size = (overlay.time.powi(3) * center.y * 14.0) as i32;
-            let position = center - rvec2(measure_text(overlay.text(), font_size), font_size) / 2.0;
-            d.draw_text(
-                overlay.text(),
-                position.x as _,
-                position.y as _,
-                font_size,
-                Color::WHITE,
-            );
-        }
-
-        if let State::LevelTransition { timer, .. } = &state {
-            d.draw_rectangle_v(
-                Vector2::zero(),
-                center * 2.0,
-                Color::new(0, 0, 0, 255 - (timer.abs().min(0.5) / 0.5 * 255.0) as u8),
-            )
-        }
-
-        let mut text = format_time(time);
-        if state == State::Paused {
-            text.push_str(" (paused)");
-        }
-        d.draw_text(&text, 10, 10, 20, Color::WHITE);
-    }
-
-    let mut timer = 0.0;
-    while !rl.window_should_close() {
-        timer += rl.get_frame_time();
-
-        fn center_text<D: RaylibDraw>(d: &mut D, text: &str, y: i32, size: i32, color: f32) {
-            d.draw_text(
-                text,
-                400 - measure_text(text, size) / 2,
-                y,
-                size,
-                Color::new(0, 0, 0, (color * 255.0) as u8),
-            );
-        }
-
-        let mut d = rl.begin_drawing(&thread);
-        let brightness = (timer / 2.0).min(1.0);
-        d.draw_texture_ex(
-            &assets.title_screen,
-            Vector2::zero(),
-            0.0,
-            3.0,
-            Color::color_from_normalized(rquat(brightness, brightness, brightness, 1.0)),
-        );
-        center_text(
-            &mut d,
-            "Thans for playing!",
-            260,
-            50,
-            (timer - 2.0).clamp(0.0, 1.0),
-        );
-        center_text(
-            &mut d,
-            &format!("Your time: {}", format_time(time)),
-            320,
-            50,
-            (timer - 3.0).clamp(0.0, 1.0),
-        );
-        center_text(
-            &mut d,
-            "Made for IcoJam 2023",
-            380,
-            30,
-            (timer - 4.0).clamp(0.0, 1.0),
-        );
-        center_text(
-            &mut d,
-            "By InfiniteCoder",
-            410,
-            40,
-            (timer - 5.0).clamp(0.0, 1.0),
-        );
+        scenes.draw(&mut d, &shared);
     }
-
-    Ok(())
+    scenes.exit(&mut shared)
 }
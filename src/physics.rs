@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use ldtk_easy::entity::Field;
+use serde::{Deserialize, Serialize};
+
+use crate::assets::*;
+
+/// Name of the physics file, it is looked for in the folder of the levels file
+pub const PHYSICS_PATH: &str = "physics.json";
+/// Copy of the physics built into the game, for when there is no physics file around
+const EMBEDDED_PHYSICS: &str = include_str!("../physics.json");
+
+/// Level fields that replace the profile's values, in the order of its fields
+pub const OVERRIDES: [&str; 9] = [
+    "Gravity",
+    "JumpSpeed",
+    "JumpCut",
+    "MaxJumps",
+    "WallJumpSpeed",
+    "RunSpeed",
+    "HalfLife",
+    "CoyoteTime",
+    "JumpBuffer",
+];
+
+/// How the player moves, speeds are in pixels per second
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsProfile {
+    pub gravity: f32,
+    pub jump_speed: f32,
+    /// Part of the upwards speed kept when letting go of jump early
+    pub jump_cut: f32,
+    /// Jumps before having to land again, counting the first one
+    pub max_jumps: u8,
+    /// Sideways speed of jumping off a wall
+    pub wall_jump_speed: f32,
+    pub run_speed: f32,
+    /// Seconds it takes to get halfway to running speed, or to stop halfway
+    pub half_life: f32,
+    /// Seconds after walking off a ledge the ground jump can still be used
+    pub coyote_time: f32,
+    /// Seconds a jump press is remembered, in case it came just before landing or grabbing a wall
+    pub jump_buffer: f32,
+}
+
+impl Default for PhysicsProfile {
+    fn default() -> Self {
+        Self {
+            gravity: 1000.0,
+            jump_speed: 300.0,
+            jump_cut: 0.5,
+            max_jumps: 2,
+            wall_jump_speed: 300.0,
+            run_speed: 160.0,
+            half_life: 0.1,
+            coyote_time: 0.1,
+            jump_buffer: 0.1,
+        }
+    }
+}
+
+impl PhysicsProfile {
+    /// Reads [`PHYSICS_PATH`] in `dir` if it exists, the embedded copy otherwise
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(PHYSICS_PATH);
+        if !path.exists() {
+            return serde_json::from_str(EMBEDDED_PHYSICS)
+                .context("Failed to parse the embedded physics!");
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read physics from '{}'!", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse physics from '{}'!", path.display()))
+    }
+
+    /// Takes the values a level sets in its [`OVERRIDES`] fields instead
+    pub fn with_overrides(mut self, level: &ldtk_easy::level::Level) -> Result<Self> {
+        let mut max_jumps = self.max_jumps as f32;
+        let values = [
+            &mut self.gravity,
+            &mut self.jump_speed,
+            &mut self.jump_cut,
+            &mut max_jumps,
+            &mut self.wall_jump_speed,
+            &mut self.run_speed,
+            &mut self.half_life,
+            &mut self.coyote_time,
+            &mut self.jump_buffer,
+        ];
+        for (name, value) in OVERRIDES.into_iter().zip(values) {
+            let field = match level.field(name) {
+                None | Some(Field::Null) => continue,
+                Some(Field::Float { value }) => value,
+                Some(Field::Int { value }) => value as f64,
+                _ => bail!(
+                    "Field '{}' of level '{}' is of unexpected type!",
+                    name,
+                    level.identifier()
+                ),
+            };
+            if let Some(problem) = override_problem(name, field) {
+                bail!("{} in level '{}'!", problem, level.identifier());
+            }
+            *value = field as f32;
+        }
+        self.max_jumps = max_jumps as u8;
+        Ok(self)
+    }
+}
+
+/// What's wrong with `value` for the override field `name`, if anything
+pub fn override_problem(name: &str, value: f64) -> Option<String> {
+    match name {
+        // The player would float away, never leave the ground or never change speed
+        "Gravity" | "JumpSpeed" | "HalfLife" if value <= 0.0 => Some(format!(
+            "Field '{}' has to be positive, not {}",
+            name, value
+        )),
+        "MaxJumps" if value.fract() != 0.0 || !(0.0..=u8::MAX as f64).contains(&value) => {
+            Some(format!(
+                "Field '{}' has to be a whole number of jumps, not {}",
+                name, value
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::level::Level;
+
+    /// Physics of the first level with its field `name` set to `value`
+    fn physics(name: &str, value: Value) -> Result<PhysicsProfile> {
+        let mut project: Value = serde_json::from_str(include_str!("../levels.ldtk")).unwrap();
+        for field in project["levels"][0]["fieldInstances"]
+            .as_array_mut()
+            .unwrap()
+        {
+            if field["__identifier"] == name {
+                field["__value"] = value.clone();
+            }
+        }
+        let levels = parse_levels(&project.to_string(), Path::new("")).unwrap();
+        Ok(Level::load(&levels, 0)?.unwrap().0.physics)
+    }
+
+    #[test]
+    fn overrides_replace_the_profile() {
+        assert_eq!(physics("MaxJumps", json!(3)).unwrap().max_jumps, 3);
+        assert_eq!(physics("Gravity", json!(500.5)).unwrap().gravity, 500.5);
+        assert_eq!(physics("HalfLife", Value::Null).unwrap().half_life, 0.1);
+    }
+
+    #[test]
+    fn impossible_overrides_are_rejected() {
+        for (name, value) in [
+            ("Gravity", json!(0)),
+            ("Gravity", json!(-1000.0)),
+            ("JumpSpeed", json!(0.0)),
+            ("HalfLife", json!(-0.1)),
+            ("MaxJumps", json!(1.5)),
+            ("MaxJumps", json!(-1)),
+            ("MaxJumps", json!(256)),
+        ] {
+            let error = physics(name, value).err().unwrap().to_string();
+            assert!(error.contains(name), "{}", error);
+            assert!(error.contains("'Level_0'"), "{}", error);
+        }
+        let error = physics("RunSpeed", json!("fast"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("'Level_0'"), "{}", error);
+    }
+}
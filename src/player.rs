@@ -1,224 +1,252 @@
 use raylib::misc::get_random_value;
 
 use crate::assets::*;
+use crate::forgiveness::MISTAKE_GRACE;
+use crate::input::{Action, Input};
 use crate::level::*;
+use crate::tile::Tile;
+use crate::world::Event;
 
+/// The sprite's edges are rounded, so they don't count when touching spikes
+const HURTBOX_INSET: f32 = 2.0;
+
+/// What the player touched during the last update
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Contacts {
+    /// What the player is standing on
+    pub ground: Option<&'static Tile>,
+}
+
+#[derive(Clone)]
 pub struct Player {
     position: Vector2,
+    previous_position: Vector2,
     size: Vector2,
     velocity: Vector2,
     jumps: u8,
-    max_jumps: u8,
+    /// Time left to use the ground jump after leaving the ground
+    coyote_time: f32,
+    /// Time left for a remembered jump press
+    jump_buffer: f32,
     holding_to_wall: bool,
+    /// Time left until wrong numbers count as mistakes again
+    mistake_grace: f32,
+    contacts: Contacts,
     frame: i8,
+    animation_timer: f32,
 }
 
 impl Player {
     pub fn new(position: Vector2, size: Vector2) -> Self {
         Self {
             position,
+            previous_position: position,
             size,
             velocity: Vector2::zero(),
             jumps: 0,
-            max_jumps: 2,
+            coyote_time: 0.0,
+            jump_buffer: 0.0,
             holding_to_wall: false,
+            mistake_grace: 0.0,
+            contacts: Contacts::default(),
             frame: 0,
+            animation_timer: 0.0,
         }
     }
 
-    pub fn collides(&self, level: &Level) -> bool {
-        let player_rect = rrect(
+    /// Box used for running into walls, slightly smaller than the sprite to fit through gaps
+    fn hitbox(&self) -> Rectangle {
+        rrect(
             self.position.x + 0.5,
             self.position.y + 0.5,
             self.size.x - 1.0,
             self.size.y - 1.0,
-        );
-        if player_rect.check_collision_recs(level.door()) {
-            return true;
-        }
-        let player_rect = rrect(
-            player_rect.x / 16.0,
-            player_rect.y / 16.0,
-            player_rect.width / 16.0,
-            player_rect.height / 16.0,
-        );
-        for y in player_rect.y as usize..=(player_rect.y + player_rect.height) as usize {
-            for x in player_rect.x as usize..=(player_rect.x + player_rect.width) as usize {
-                if match level.tile(rvec2(x as f32, y as f32)) {
-                    Tile::Empty => false,
-                    Tile::Ground => true,
-                    Tile::Spike => false,
-                } {
-                    return true;
-                }
-            }
-        }
-        player_rect.x < 0.0
-            || player_rect.y < 0.0
-            || player_rect.y + player_rect.height >= level.size().y / 16.0
-    }
-
-    pub fn collidable_move(&mut self, rl: &mut RaylibHandle, level: &Level, direction: Vector2) {
-        let motion = self.velocity * direction * rl.get_frame_time();
-        self.position += motion;
-        if self.collides(level) {
-            loop {
-                self.position -= rvec2(self.velocity.x.signum(), self.velocity.y.signum())
-                    * direction
-                    * motion.length();
-                if !self.collides(level) {
-                    break;
-                }
-            }
+        )
+    }
+
+    pub fn collides(&self, level: &Level) -> bool {
+        level.overlapping(self.hitbox()).is_some()
+    }
 
+    pub fn collidable_move(&mut self, dt: f32, input: &Input, level: &Level, direction: Vector2) {
+        let motion = self.velocity * direction * dt;
+        let (moved, hit) = level.sweep(self.hitbox(), motion);
+        self.position += moved;
+        if let Some(tile) = hit {
             if direction.x != 0.0 {
-                if self.keyboard_joy(rl) as f32 == motion.x.signum()
-                    && (rl.is_key_down(KeyboardKey::KEY_S) || rl.is_key_down(KeyboardKey::KEY_DOWN))
-                {
+                if input.joy() as f32 == motion.x.signum() && input.down(Action::Grab) {
                     self.holding_to_wall = true;
                 }
-                self.velocity.x = 0.0;
+                self.velocity.x = tile.bounce(self.velocity.x);
             }
             if direction.y != 0.0 {
-                self.velocity.y = 0.0;
+                self.velocity.y = tile.bounce(self.velocity.y);
                 if motion.y > 0.0 {
-                    self.jumps = self.max_jumps;
+                    self.jumps = level.physics.max_jumps;
+                    self.contacts.ground = Some(tile);
                 }
             }
-        } else if motion.x != 0.0
-            || self.keyboard_joy(rl) as f32 == 0.0
-            || (rl.is_key_released(KeyboardKey::KEY_S) || rl.is_key_released(KeyboardKey::KEY_DOWN))
-        {
+        } else if motion.x != 0.0 || input.joy() as f32 == 0.0 || input.released(Action::Grab) {
             self.holding_to_wall = false;
         }
     }
 
-    pub fn update(
-        &mut self,
-        assets: &mut Assets,
-        rl: &mut RaylibHandle,
-        level: &mut Level,
-        state: &mut State,
-    ) {
+    pub fn update(&mut self, dt: f32, input: &Input, level: &mut Level, events: &mut Vec<Event>) {
+        let physics = level.physics;
         // * Jump
-        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) && (self.jumps > 0 || self.holding_to_wall) {
+        // Walking off a ledge only keeps the air jumps, once it's too late for the ground jump
+        if self.contacts.ground.is_none() && self.coyote_time <= 0.0 {
+            self.jumps = self.jumps.min(physics.max_jumps.saturating_sub(1));
+        }
+        self.jump_buffer = if input.pressed(Action::Jump) {
+            physics.jump_buffer
+        } else {
+            self.jump_buffer - dt
+        };
+        if (input.pressed(Action::Jump) || self.jump_buffer > 0.0)
+            && (self.jumps > 0 || self.holding_to_wall)
+        {
             if self.holding_to_wall {
-                self.velocity.x = self.keyboard_joy(rl) as f32 * -300.0;
+                self.velocity.x = input.joy() as f32 * -physics.wall_jump_speed;
                 self.holding_to_wall = false;
             } else if self.jumps > 0 {
                 self.jumps -= 1;
+                self.coyote_time = 0.0;
+            }
+            self.velocity.y = -physics.jump_speed;
+            // A remembered press might have been let go of already, releasing now is handled below
+            if !input.down(Action::Jump) && !input.released(Action::Jump) {
+                self.velocity.y *= physics.jump_cut;
             }
-            self.velocity.y = -300.0;
-            assets.audio.play_sound(&assets.jump_sound);
+            self.jump_buffer = 0.0;
+            events.push(Event::Jump);
         }
 
-        if rl.is_key_released(KeyboardKey::KEY_SPACE) && self.velocity.y < 0.0 {
-            self.velocity.y *= 0.5;
+        if input.released(Action::Jump) && self.velocity.y < 0.0 {
+            self.velocity.y *= physics.jump_cut;
         }
 
         // Gravity
-        self.velocity.y += 1000.0 * rl.get_frame_time();
+        self.velocity.y += physics.gravity * dt;
 
         if self.holding_to_wall {
             self.velocity.y *= 0.0;
         }
 
         // * Integration
-        let target_velocity = self.keyboard_joy(rl) as f32 * self.size.x * 10.0;
+        let target_velocity = input.joy() as f32 * physics.run_speed;
+        // Slippery ground takes longer to speed up and slow down on
+        let friction = self.contacts.ground.map_or(1.0, |tile| tile.friction);
 
-        self.velocity.x +=
-            (target_velocity - self.velocity.x) * (1.0 - 0.5_f32.powf(rl.get_frame_time() / 0.1));
+        self.velocity.x += (target_velocity - self.velocity.x)
+            * (1.0 - 0.5_f32.powf(dt * friction / physics.half_life));
 
-        while self.collides(level) {
-            self.position.y -= 0.5;
+        // Something moved into the player (like the door opening below), so climb on top of it
+        while let Some(obstacle) = level.overlapping(self.hitbox()) {
+            let hitbox = self.hitbox();
+            self.position.y -= hitbox.y + hitbox.height - obstacle.y;
         }
+        self.contacts = Contacts::default();
 
         // self.holding_to_wall = false;
 
-        self.collidable_move(rl, level, rvec2(1, 0));
-        self.collidable_move(rl, level, rvec2(0, 1));
-        self.check_interactibles(assets, level, state);
+        self.collidable_move(dt, input, level, rvec2(1, 0));
+        self.collidable_move(dt, input, level, rvec2(0, 1));
+        self.coyote_time = if self.contacts.ground.is_some() {
+            physics.coyote_time
+        } else {
+            self.coyote_time - dt
+        };
+        self.mistake_grace -= dt;
+        self.check_interactibles(level, events);
 
+        self.animation_timer += dt;
         if self.velocity.x.abs() > 10.0 {
-            self.frame = self.velocity.x.signum() as i8 * (rl.get_time() * 20.0 % 2.0 + 1.0) as i8;
+            self.frame =
+                self.velocity.x.signum() as i8 * (self.animation_timer * 20.0 % 2.0 + 1.0) as i8;
         } else if self.holding_to_wall {
-            self.frame = self.keyboard_joy(rl) * 3;
+            self.frame = input.joy() * 3;
         } else {
             self.frame = 0;
         }
     }
 
-    fn keyboard_joy(&mut self, rl: &mut RaylibHandle) -> i8 {
-        (rl.is_key_down(KeyboardKey::KEY_D) || rl.is_key_down(KeyboardKey::KEY_RIGHT)) as i8
-            - (rl.is_key_down(KeyboardKey::KEY_A) || rl.is_key_down(KeyboardKey::KEY_LEFT)) as i8
+    /// Blows the player up, the attempt is over
+    pub fn die(&self, level: &mut Level, events: &mut Vec<Event>) {
+        explode(level, self.center(), 200, 200, Color::RED);
+        events.push(Event::GameOver);
     }
 
-    fn check_interactibles(&self, assets: &mut Assets, level: &mut Level, state: &mut State) {
+    fn check_interactibles(&mut self, level: &mut Level, events: &mut Vec<Event>) {
         let player_rect = rrect(self.position.x, self.position.y, self.size.x, self.size.y);
-        fn explode(level: &mut Level, center: Vector2, count: usize, power: i32, color: Color) {
-            for _ in 0..count {
-                let velocity = tuple2(
-                    (get_random_value::<i32>(-60, 60) as f32 * std::f32::consts::PI / 180.0)
-                        .sin_cos(),
-                ) * rvec2(1, -1)
-                    * get_random_value::<i32>(0, power) as f32;
-                level
-                    .particles
-                    .push(Particle::new(center, velocity, 1.0, color));
-            }
-        }
-
-        fn game_over(assets: &mut Assets, level: &mut Level, state: &mut State, center: Vector2) {
-            *state = State::transition(level.index());
-            explode(level, center, 200, 200, Color::RED);
-            assets.audio.play_sound(&assets.game_over_sound);
-        }
-
         for i in 0..level.numbers.len() {
             let number = &level.numbers[i];
             if number.rect().check_collision_recs(&player_rect) {
-                if level.current_number != number.number() {
-                    game_over(assets, level, state, self.center());
+                let track = number.track();
+                if level.tracks[track].sequence.next() != Some(number.number()) {
+                    // Still touching the number from the last mistake
+                    if self.mistake_grace > 0.0 {
+                        continue;
+                    }
+                    // What a mistake costs depends on the forgiveness mode, the world decides
+                    self.mistake_grace = MISTAKE_GRACE;
+                    explode(level, self.center(), 40, 140, Color::RED);
+                    events.push(Event::Mistake);
                 } else {
                     level
                         .overlays
                         .push(Overlay::new(number.number().to_string()));
-                    level.current_number -= 1;
+                    level.tracks[track].sequence.advance();
                     explode(level, number.center(), 20, 140, Color::WHITE);
                     level.numbers.remove(i);
-                    assets.audio.play_sound(&assets.number_sound);
+                    events.push(Event::Number);
                 }
                 break;
             }
         }
 
-        let player_rect = rrect(
-            player_rect.x / 16.0 + 0.25,
-            player_rect.y / 16.0 + 0.25,
-            player_rect.width / 16.0 - 0.5,
-            player_rect.height / 16.0 - 0.5,
+        let hurtbox = rrect(
+            player_rect.x + HURTBOX_INSET,
+            player_rect.y + HURTBOX_INSET,
+            player_rect.width - HURTBOX_INSET * 2.0,
+            player_rect.height - HURTBOX_INSET * 2.0,
         );
-
-        for y in player_rect.y as usize..=(player_rect.y + player_rect.height) as usize {
-            for x in player_rect.x as usize..=(player_rect.x + player_rect.width) as usize {
-                if level.tile(rvec2(x as f32, y as f32)) == Tile::Spike {
-                    game_over(assets, level, state, self.center());
+        for y in (hurtbox.y / 16.0) as usize..=((hurtbox.y + hurtbox.height) / 16.0) as usize {
+            for x in (hurtbox.x / 16.0) as usize..=((hurtbox.x + hurtbox.width) / 16.0) as usize {
+                let position = rvec2(x as f32, y as f32);
+                let cell = level.cell(position);
+                if cell.tile.damage && cell.touches(position * 16.0, hurtbox) {
+                    self.die(level, events);
+                    return;
                 }
             }
         }
     }
 
-    pub fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D) {
+    pub fn draw<D: RaylibDraw>(&self, assets: &Assets, d: &mut D, alpha: f32, tint: Color) {
+        self.draw_frame(
+            assets,
+            d,
+            self.previous_position.lerp(self.position, alpha),
+            self.frame,
+            tint,
+        );
+    }
+
+    /// Draw the player's sprite somewhere else, e.g. for a ghost
+    pub fn draw_frame<D: RaylibDraw>(
+        &self,
+        assets: &Assets,
+        d: &mut D,
+        position: Vector2,
+        frame: i8,
+        tint: Color,
+    ) {
         d.draw_texture_rec(
             &assets.player,
-            rrect(
-                (self.frame + 3) * self.size.x as i8,
-                0,
-                self.size.x,
-                self.size.y,
-            ),
-            self.position,
-            Color::WHITE,
+            rrect((frame + 3) * self.size.x as i8, 0, self.size.x, self.size.y),
+            position,
+            tint,
         );
     }
 
@@ -239,7 +267,268 @@ impl Player {
         self.position
     }
 
-    fn center(&self) -> Vector2 {
+    pub fn velocity(&self) -> Vector2 {
+        self.velocity
+    }
+
+    pub fn jumps(&self) -> u8 {
+        self.jumps
+    }
+
+    pub fn holding_to_wall(&self) -> bool {
+        self.holding_to_wall
+    }
+
+    /// Whether a mistake was made just now, and wrong numbers are ignored for a moment
+    pub fn recovering(&self) -> bool {
+        self.mistake_grace > 0.0
+    }
+
+    pub fn contacts(&self) -> Contacts {
+        self.contacts
+    }
+
+    pub fn frame(&self) -> i8 {
+        self.frame
+    }
+
+    pub fn save_position(&mut self) {
+        self.previous_position = self.position;
+    }
+
+    pub fn center(&self) -> Vector2 {
         self.position + self.size / 2.0
     }
 }
+
+fn explode(level: &mut Level, center: Vector2, count: usize, power: i32, color: Color) {
+    for _ in 0..count {
+        let velocity = tuple2(
+            (get_random_value::<i32>(-60, 60) as f32 * std::f32::consts::PI / 180.0).sin_cos(),
+        ) * rvec2(1, -1)
+            * get_random_value::<i32>(0, power) as f32;
+        level
+            .particles
+            .push(Particle::new(center, velocity, 1.0, color));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::PhysicsProfile;
+    use crate::world::TICK;
+
+    const SIZE: Vector2 = Vector2 { x: 16.0, y: 24.0 };
+    const OPEN: [&str; 8] = ["........"; 8];
+    const LEDGE: [&str; 8] = [
+        "........", "........", "###.....", "........", "........", "........", "........",
+        "........",
+    ];
+    const WALL: [&str; 8] = ["#......."; 8];
+    /// Ticks a buffered jump is pressed before it can be used
+    const EARLY: usize = 4;
+
+    /// Steps with `down` held, returning whether the player jumped
+    fn step(player: &mut Player, level: &mut Level, input: &mut Input, down: u16) -> bool {
+        *input = input.next(down);
+        let mut events = Vec::new();
+        player.update(TICK, input, level, &mut events);
+        events.contains(&Event::Jump)
+    }
+
+    /// Steps with `down` held until `done`, returning how many steps it took
+    fn step_until(
+        player: &mut Player,
+        level: &mut Level,
+        input: &mut Input,
+        down: u16,
+        done: impl Fn(&Player) -> bool,
+    ) -> usize {
+        for ticks in 1..=(1.0 / TICK) as usize {
+            step(player, level, input, down);
+            if done(player) {
+                return ticks;
+            }
+        }
+        panic!("Took longer than a second!");
+    }
+
+    /// Taps jump until it does nothing anymore, returning how many jumps there were
+    fn count_jumps(player: &mut Player, level: &mut Level, input: &mut Input) -> u8 {
+        let mut jumps = 0;
+        while step(player, level, input, Action::Jump.mask()) {
+            jumps += 1;
+            step(player, level, input, 0);
+        }
+        jumps
+    }
+
+    /// Lands on the ledge and walks right until there's nothing underneath anymore
+    fn walk_off_ledge(physics: PhysicsProfile) -> (Player, Level, Input) {
+        let mut level = Level::from_rows(&LEDGE, physics);
+        let mut player = Player::new(rvec2(16, 8), SIZE);
+        let mut input = Input::default();
+        step_until(&mut player, &mut level, &mut input, 0, |player| {
+            player.contacts().ground.is_some()
+        });
+        step_until(
+            &mut player,
+            &mut level,
+            &mut input,
+            Action::Right.mask(),
+            |player| player.contacts().ground.is_none(),
+        );
+        (player, level, input)
+    }
+
+    /// Holds `down` from `position` and presses jump a few ticks before the player lands or
+    /// grabs a wall, letting go of it right away if `tap`. Returns the player after the tick
+    /// following the contact and whether it jumped then.
+    fn buffered_jump(rows: &[&str], position: Vector2, down: u16, tap: bool) -> (Player, bool) {
+        let physics = PhysicsProfile {
+            max_jumps: 1,
+            ..Default::default()
+        };
+        let mut level = Level::from_rows(rows, physics);
+        let touching =
+            |player: &Player| player.contacts().ground.is_some() || player.holding_to_wall;
+        // Pressing jump in the air without jumps left doesn't change the path, so it's the same
+        let ticks = step_until(
+            &mut Player::new(position, SIZE),
+            &mut level.clone(),
+            &mut Input::default(),
+            down,
+            touching,
+        );
+        assert!(ticks > EARLY);
+        let mut player = Player::new(position, SIZE);
+        let mut input = Input::default();
+        for tick in 1..=ticks {
+            let held = if tap {
+                tick == ticks - EARLY
+            } else {
+                tick >= ticks - EARLY
+            };
+            let jump = if held { Action::Jump.mask() } else { 0 };
+            assert!(!step(&mut player, &mut level, &mut input, down | jump));
+        }
+        assert!(touching(&player));
+        let jump = if tap { 0 } else { Action::Jump.mask() };
+        let jumped = step(&mut player, &mut level, &mut input, down | jump);
+        (player, jumped)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn ground_jump_right_after_walking_off() {
+        let physics = PhysicsProfile {
+            max_jumps: 3,
+            ..Default::default()
+        };
+        let (mut player, mut level, mut input) = walk_off_ledge(physics);
+        assert_eq!(
+            count_jumps(&mut player, &mut level, &mut input),
+            physics.max_jumps
+        );
+    }
+
+    #[test]
+    fn only_air_jumps_once_coyote_time_is_over() {
+        let physics = PhysicsProfile {
+            max_jumps: 3,
+            ..Default::default()
+        };
+        let (mut player, mut level, mut input) = walk_off_ledge(physics);
+        for _ in 0..=(physics.coyote_time / TICK) as usize {
+            step(&mut player, &mut level, &mut input, 0);
+        }
+        assert!(player.contacts().ground.is_none());
+        assert_eq!(
+            count_jumps(&mut player, &mut level, &mut input),
+            physics.max_jumps - 1
+        );
+    }
+
+    #[test]
+    fn buffered_jump_on_landing() {
+        let physics = PhysicsProfile::default();
+        let (player, jumped) = buffered_jump(&OPEN, rvec2(64, 16), 0, false);
+        assert!(jumped);
+        assert_close(
+            player.velocity().y,
+            -physics.jump_speed + physics.gravity * TICK,
+        );
+    }
+
+    #[test]
+    fn buffered_jump_on_grabbing_a_wall() {
+        let down = Action::Left.mask() | Action::Grab.mask();
+        let (player, jumped) = buffered_jump(&WALL, rvec2(40, 16), down, false);
+        assert!(jumped);
+        assert!(player.velocity().x > 0.0);
+        assert!(player.velocity().y < 0.0);
+    }
+
+    #[test]
+    fn released_buffered_jump_is_cut() {
+        let physics = PhysicsProfile::default();
+        let (player, jumped) = buffered_jump(&OPEN, rvec2(64, 16), 0, true);
+        assert!(jumped);
+        assert_close(
+            player.velocity().y,
+            -physics.jump_speed * physics.jump_cut + physics.gravity * TICK,
+        );
+    }
+
+    /// A room closed on the right, with a column two tiles high on the floor
+    const ROOM: [&str; 8] = [
+        ".......#", ".......#", ".......#", ".......#", ".......#", "...#...#", "...#...#",
+        "########",
+    ];
+
+    /// The player at `position` after a single update, with `velocity` and nothing held
+    fn moved(position: Vector2, velocity: Vector2) -> (Player, Level) {
+        let mut level = Level::from_rows(&ROOM, PhysicsProfile::default());
+        let mut player = Player::new(position, SIZE);
+        player.velocity = velocity;
+        step(&mut player, &mut level, &mut Input::default(), 0);
+        assert!(!player.collides(&level));
+        (player, level)
+    }
+
+    #[test]
+    fn climbs_on_top_of_what_it_is_inside() {
+        // Stuck in both tiles of the column, it ends up standing on the top one
+        let (player, _) = moved(rvec2(48, 80), Vector2::zero());
+        assert_close(player.hitbox().y + player.hitbox().height, 80.0);
+        assert!(player.contacts().ground.is_some());
+        // Sinking into the floor of the level
+        let (player, _) = moved(rvec2(16, 100), Vector2::zero());
+        assert_close(player.hitbox().y + player.hitbox().height, 112.0);
+    }
+
+    #[test]
+    fn never_tunnels() {
+        let fast = 100_000.0;
+        // Through the column, one tile thin
+        let (player, _) = moved(rvec2(0, 88), rvec2(fast, 0));
+        assert_close(player.hitbox().x + player.hitbox().width, 48.0);
+        let (player, _) = moved(rvec2(64, 88), rvec2(-fast, 0));
+        assert_close(player.hitbox().x, 64.0);
+        // Onto the column from above
+        let (player, _) = moved(rvec2(48, 0), rvec2(0, fast));
+        assert_close(player.hitbox().y + player.hitbox().height, 80.0);
+        // Into a corner
+        let (player, _) = moved(rvec2(64, 0), rvec2(fast, fast));
+        assert_close(player.hitbox().x + player.hitbox().width, 112.0);
+        assert_close(player.hitbox().y + player.hitbox().height, 112.0);
+        // Out of the level on the left or at the top
+        let (player, _) = moved(rvec2(16, 16), rvec2(-fast, -fast));
+        assert_close(player.hitbox().x, 0.0);
+        assert_close(player.hitbox().y, 0.0);
+    }
+}
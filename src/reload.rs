@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How often the file is checked, in seconds
+const INTERVAL: f32 = 0.5;
+
+/// Notices when files are saved, created or deleted by polling their modification times
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    timer: f32,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            modified: paths.iter().map(|path| modified(path)).collect(),
+            paths,
+            timer: 0.0,
+        }
+    }
+
+    /// Returns true once after every change to any of the files
+    pub fn changed(&mut self, dt: f32) -> bool {
+        self.timer += dt;
+        if self.timer < INTERVAL {
+            return false;
+        }
+        self.timer = 0.0;
+
+        let modified: Vec<_> = self.paths.iter().map(|path| modified(path)).collect();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
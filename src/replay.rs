@@ -0,0 +1,198 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::input::Input;
+
+const MAGIC: &[u8; 4] = b"CTCR";
+/// Bump whenever the file layout or the simulation changes in a way that breaks old replays
+pub const VERSION: u16 = 6;
+
+/// Ticks spent on one level, from loading it until the next one is loaded
+#[derive(Debug, Clone, Default)]
+pub struct Segment {
+    pub level: usize,
+    pub ticks: Vec<Input>,
+}
+
+/// Every input that was fed into the simulation during a run
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    pub segments: Vec<Segment>,
+    /// Game time at the end of the run, to check the replay against
+    pub time: f32,
+    /// Respawns take a different number of ticks depending on the fade
+    pub respawn_fade: f32,
+    /// Mistakes play out differently in every mode
+    pub forgiveness: Forgiveness,
+    /// [`Levels::hash`] of the levels the replay was recorded on
+    pub levels_hash: u64,
+}
+
+impl Replay {
+    pub fn record(&mut self, level: usize, input: &Input) {
+        match self.segments.last_mut() {
+            Some(segment) if segment.level == level => segment.ticks.push(*input),
+            _ => self.segments.push(Segment {
+                level,
+                ticks: vec![*input],
+            }),
+        }
+    }
+
+    pub fn start_level(&self) -> usize {
+        self.segments.first().map_or(0, |segment| segment.level)
+    }
+
+    /// All inputs in order, together with the level they were recorded on
+    pub fn inputs(&self) -> impl Iterator<Item = (usize, Input)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            segment
+                .ticks
+                .iter()
+                .map(move |input| (segment.level, *input))
+        })
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.time.to_le_bytes())?;
+        writer.write_all(&self.respawn_fade.to_le_bytes())?;
+        writer.write_all(&[self.forgiveness.to_byte()])?;
+        writer.write_all(&self.levels_hash.to_le_bytes())?;
+        writer.write_all(&(self.segments.len() as u32).to_le_bytes())?;
+        for segment in &self.segments {
+            writer.write_all(&(segment.level as u32).to_le_bytes())?;
+            writer.write_all(&(segment.ticks.len() as u32).to_le_bytes())?;
+            for input in &segment.ticks {
+                writer.write_all(&input.to_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> Result<Self> {
+        fn read_u32(reader: &mut impl Read) -> Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "Not a replay file!");
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        // Older replays ran on a different simulation and would only desync
+        ensure!(
+            version == VERSION,
+            "Replay version {} is not supported (expected {})!",
+            version,
+            VERSION
+        );
+
+        let time = f32::from_bits(read_u32(&mut reader)?);
+        let respawn_fade = f32::from_bits(read_u32(&mut reader)?);
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        let forgiveness = Forgiveness::from_byte(byte[0])
+            .with_context(|| format!("Unknown forgiveness mode {}!", byte[0]))?;
+        let mut hash = [0; 8];
+        reader.read_exact(&mut hash)?;
+        let levels_hash = u64::from_le_bytes(hash);
+        let mut segments = Vec::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let level = read_u32(&mut reader)? as usize;
+            let mut ticks = Vec::new();
+            for _ in 0..read_u32(&mut reader)? {
+                let mut bytes = [0; 3];
+                reader.read_exact(&mut bytes)?;
+                ticks.push(Input::from_bytes(bytes));
+            }
+            segments.push(Segment { level, ticks });
+        }
+        Ok(Self {
+            segments,
+            time,
+            respawn_fade,
+            forgiveness,
+            levels_hash,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::read(std::io::BufReader::new(
+            std::fs::File::open(path)
+                .with_context(|| format!("Failed to open replay '{}'!", path.display()))?,
+        ))
+        .with_context(|| format!("Failed to read replay '{}'!", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+
+    fn replay() -> Replay {
+        let mut replay = Replay {
+            time: 12.5,
+            respawn_fade: 0.25,
+            forgiveness: Forgiveness::Lives,
+            levels_hash: 0x0123456789abcdef,
+            ..Replay::default()
+        };
+        let mut input = Input::default();
+        for (level, down) in [(0, Action::Right.mask()), (0, 0), (1, Action::Jump.mask())] {
+            input = input.next(down);
+            replay.record(level, &input);
+        }
+        replay
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("catch-the-countdown-{}", std::process::id()))
+            .join("round_trip.ctcr");
+        let replay = replay();
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(loaded.time, replay.time);
+        assert_eq!(loaded.respawn_fade, replay.respawn_fade);
+        assert_eq!(loaded.forgiveness, Forgiveness::Lives);
+        assert_eq!(loaded.levels_hash, replay.levels_hash);
+        assert_eq!(loaded.start_level(), 0);
+        assert_eq!(
+            loaded.inputs().collect::<Vec<_>>(),
+            replay.inputs().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = Vec::new();
+        replay().write(&mut bytes).unwrap();
+        for version in [1, VERSION - 1, VERSION + 1] {
+            bytes[4..6].copy_from_slice(&version.to_le_bytes());
+            assert!(Replay::read(bytes.as_slice()).is_err());
+        }
+    }
+}
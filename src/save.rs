@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::ghost::GhostMode;
+use crate::timing::{Timed, TimingMethod};
+use crate::world::LEVEL_FADE;
+
+/// Bump when the layout changes in a way `#[serde(default)]` can't handle
+pub const VERSION: u32 = 1;
+const FILE_NAME: &str = "save.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub music: bool,
+    pub timing: TimingMethod,
+    pub ghost: GhostMode,
+    /// Seconds of fading out (and back in) when respawning, 0 is instant
+    pub respawn_fade: f32,
+    /// What touching a wrong number does, for runs started from now on
+    pub forgiveness: Forgiveness,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music: false,
+            timing: TimingMethod::RealTime,
+            ghost: GhostMode::Level,
+            respawn_fade: LEVEL_FADE,
+            forgiveness: Forgiveness::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelRecord {
+    /// Fastest attempt from (re)loading the level to leaving it, in normal mode
+    pub best_time: Option<f32>,
+    /// The same for the other forgiveness modes
+    pub mode_times: BTreeMap<Forgiveness, f32>,
+    pub deaths: u32,
+}
+
+impl LevelRecord {
+    pub fn best_time(&self, mode: Forgiveness) -> Option<f32> {
+        match mode {
+            Forgiveness::Normal => self.best_time,
+            mode => self.mode_times.get(&mode).copied(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveData {
+    pub version: u32,
+    /// Number of levels that can be played, counting from the first one
+    pub unlocked_levels: usize,
+    pub levels: Vec<LevelRecord>,
+    /// Fastest full run in normal mode
+    pub best_run: Timed<Option<f32>>,
+    /// The same for the other forgiveness modes
+    pub mode_runs: BTreeMap<Forgiveness, Timed<Option<f32>>>,
+    pub settings: Settings,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            unlocked_levels: 1,
+            levels: Vec::new(),
+            best_run: Timed::default(),
+            mode_runs: BTreeMap::new(),
+            settings: Settings::default(),
+        }
+    }
+}
+
+impl SaveData {
+    pub fn level(&mut self, level: usize) -> &mut LevelRecord {
+        if level >= self.levels.len() {
+            self.levels.resize(level + 1, LevelRecord::default());
+        }
+        &mut self.levels[level]
+    }
+
+    pub fn complete_level(&mut self, level: usize, time: f32, mode: Forgiveness) {
+        let record = self.level(level);
+        if !matches!(record.best_time(mode), Some(best) if best <= time) {
+            match mode {
+                Forgiveness::Normal => record.best_time = Some(time),
+                mode => _ = record.mode_times.insert(mode, time),
+            }
+        }
+        self.unlocked_levels = self.unlocked_levels.max(level + 2);
+    }
+
+    pub fn finish_run(&mut self, time: Timed<f32>, mode: Forgiveness) {
+        let best_run = match mode {
+            Forgiveness::Normal => &mut self.best_run,
+            mode => self.mode_runs.entry(mode).or_default(),
+        };
+        for method in [TimingMethod::RealTime, TimingMethod::GameTime] {
+            let best = best_run.get_mut(method);
+            if !matches!(best, Some(best) if *best <= *time.get(method)) {
+                *best = Some(*time.get(method));
+            }
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let data: Self = serde_json::from_str(text)?;
+        ensure!(
+            data.version <= VERSION,
+            "Save data version {} is newer than this game ({})!",
+            data.version,
+            VERSION
+        );
+        Ok(Self {
+            version: VERSION,
+            ..data
+        })
+    }
+
+    /// Loads the save data, falling back to the backup and then to a fresh save
+    /// if the file is missing or corrupted
+    pub fn load() -> Self {
+        for name in [FILE_NAME.to_owned(), backup_name()] {
+            let data = storage::read(&name)
+                .and_then(|text| text.map(|text| Self::parse(&text)).transpose());
+            if let Err(err) = &data {
+                eprintln!("Failed to load '{}', trying the backup: {:?}", name, err);
+                storage::keep_corrupted(&name);
+            } else if let Some(data) = data.ok().flatten() {
+                return data;
+            }
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        storage::write_atomic(FILE_NAME, &backup_name(), &text).context("Failed to save game!")
+    }
+}
+
+/// Saving shouldn't end the game (e.g. on a full disk or in a read-only directory),
+/// and neither should a broken file, so failures are only logged
+pub fn log_failure<T>(result: Result<T>) -> Option<T> {
+    result.map_err(|err| eprintln!("{:?}", err)).ok()
+}
+
+/// Writes to a temporary file next to `path` first, so a crash never leaves a half-written file
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut name = path.file_name().context("No file name!")?.to_owned();
+    name.push(".tmp");
+    let temporary = path.with_file_name(name);
+
+    let mut file = std::fs::File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+fn backup_name() -> String {
+    format!("{}.bak", FILE_NAME)
+}
+
+/// Directory for everything the game writes: save data, splits, ghosts and replays.
+/// On the web it only lives in memory, and just the save data is kept in local storage,
+/// so splits, ghosts and replays are gone after reloading the page.
+pub fn data_dir() -> PathBuf {
+    const NAME: &str = "catch-the-countdown";
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    if cfg!(target_os = "emscripten") {
+        // Only lives in memory, persistent data goes to local storage
+        PathBuf::from("/data")
+    } else if cfg!(target_os = "windows") {
+        env("APPDATA")
+            .map_or_else(|| PathBuf::from("."), PathBuf::from)
+            .join(NAME)
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map_or_else(
+            || PathBuf::from("."),
+            |home| {
+                PathBuf::from(home)
+                    .join("Library/Application Support")
+                    .join(NAME)
+            },
+        )
+    } else if let Some(data) = env("XDG_DATA_HOME") {
+        PathBuf::from(data).join(NAME)
+    } else if let Some(home) = env("HOME") {
+        PathBuf::from(home).join(".local/share").join(NAME)
+    } else {
+        PathBuf::from(".")
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+mod storage {
+    use super::data_dir;
+    use crate::assets::*;
+
+    pub fn read(name: &str) -> Result<Option<String>> {
+        let path = data_dir().join(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?))
+    }
+
+    /// Keeps the previous save as a backup before replacing it
+    pub fn write_atomic(name: &str, backup: &str, text: &str) -> Result<()> {
+        let dir = data_dir();
+        let path = dir.join(name);
+        if path.exists() {
+            std::fs::copy(&path, dir.join(backup))?;
+        }
+        super::write_atomic(&path, text.as_bytes())
+    }
+
+    /// Move a broken file out of the way so it can be inspected later
+    pub fn keep_corrupted(name: &str) {
+        let dir = data_dir();
+        let _ = std::fs::rename(dir.join(name), dir.join(format!("{}.corrupted", name)));
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+mod storage {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+
+    use crate::assets::*;
+
+    extern "C" {
+        fn emscripten_run_script(script: *const c_char);
+        fn emscripten_run_script_string(script: *const c_char) -> *const c_char;
+    }
+
+    fn key(name: &str) -> Result<String> {
+        Ok(serde_json::to_string(&format!(
+            "catch-the-countdown/{}",
+            name
+        ))?)
+    }
+
+    fn run(script: &str) -> Result<()> {
+        let script = CString::new(script)?;
+        unsafe { emscripten_run_script(script.as_ptr()) };
+        Ok(())
+    }
+
+    pub fn read(name: &str) -> Result<Option<String>> {
+        let script = CString::new(format!(
+            "(function() {{ var value = localStorage.getItem({}); return value === null ? '' : 'v' + value; }})()",
+            key(name)?
+        ))?;
+        let value = unsafe { CStr::from_ptr(emscripten_run_script_string(script.as_ptr())) };
+        Ok(value.to_str()?.strip_prefix('v').map(str::to_owned))
+    }
+
+    /// Local storage writes are atomic already, but keep a backup like on desktop
+    pub fn write_atomic(name: &str, backup: &str, text: &str) -> Result<()> {
+        run(&format!(
+            "(function() {{ var value = localStorage.getItem({0}); if (value !== null) localStorage.setItem({1}, value); localStorage.setItem({0}, {2}); }})()",
+            key(name)?,
+            key(backup)?,
+            serde_json::to_string(text)?
+        ))
+    }
+
+    pub fn keep_corrupted(name: &str) {
+        let _ = key(name).and_then(|key| {
+            run(&format!(
+                "(function() {{ var value = localStorage.getItem({0}); localStorage.setItem({1}, value); localStorage.removeItem({0}); }})()",
+                key,
+                serde_json::to_string(&format!("catch-the-countdown/{}.corrupted", name))?
+            ))
+        });
+    }
+}
@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::ghost::Ghosts;
+use crate::input::{Bindings, Input};
+use crate::save::{log_failure, SaveData};
+use crate::splits::Splits;
+
+/// How long fading out (and back in) around a transition takes
+const FADE_TIME: f32 = 0.25;
+
+/// Everything scenes share, loaded once at startup
+pub struct Shared {
+    pub assets: Assets,
+    pub save: SaveData,
+    pub splits: Splits,
+    /// Splits of normal runs, the other forgiveness modes keep theirs next to them
+    pub splits_path: PathBuf,
+    /// Which mode `splits` were loaded for, `None` before the first load
+    pub splits_mode: Option<Forgiveness>,
+    pub best_ghosts: Ghosts,
+    pub bindings: Bindings,
+    /// Menu input, polled once a frame before the scenes update
+    pub input: Input,
+}
+
+impl Shared {
+    /// File the splits of `mode` are saved in
+    pub fn splits_file(&self, mode: Forgiveness) -> PathBuf {
+        if mode == Forgiveness::Normal {
+            return self.splits_path.clone();
+        }
+        let stem = self.splits_path.file_stem().unwrap_or_default();
+        self.splits_path
+            .with_file_name(format!("{}-{}.lss", stem.to_string_lossy(), mode.key()))
+    }
+
+    /// Swaps in the splits of `mode`, unless they're loaded already.
+    /// Broken splits are logged and replaced with empty ones.
+    pub fn use_splits(&mut self, mode: Forgiveness) {
+        if self.splits_mode == Some(mode) {
+            return;
+        }
+        let level_count = self.assets.world.project.levels().len();
+        self.splits =
+            log_failure(Splits::load(self.splits_file(mode)).context("Failed to load splits!"))
+                .flatten()
+                .unwrap_or_else(|| Splits::new(level_count));
+        self.splits.resize(level_count);
+        self.splits_mode = Some(mode);
+    }
+
+    /// Swaps in reloaded levels, with a split and a ghost for every level
+    pub fn swap_levels(&mut self, levels: Levels) {
+        let level_count = levels.project.levels().len();
+        self.assets.world = levels;
+        self.splits.resize(level_count);
+        self.best_ghosts.levels.resize(level_count, None);
+    }
+}
+
+/// Handed to the scene below when the top one is popped with [`Transition::Return`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Message {
+    RestartLevel,
+}
+
+/// What the scene stack should do after an update
+pub enum Transition {
+    None,
+    Push(Box<dyn Scene>),
+    Pop,
+    /// Pop and tell the scene below what to do next
+    Return(Message),
+    Replace(Box<dyn Scene>),
+    /// Drop every scene and start over from this one
+    Reset(Box<dyn Scene>),
+}
+
+pub trait Scene {
+    /// `focused` is false while another scene is on top of this one,
+    /// in which case it shouldn't react to input
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition>;
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared);
+
+    /// Overlays are drawn (and updated) on top of the scene below them
+    fn overlay(&self) -> bool {
+        false
+    }
+
+    /// Called when the scene on top of this one was popped
+    fn resume(&mut self, _shared: &mut Shared, _message: Option<Message>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with levels reloaded from disk, before they replace the old ones.
+    /// If any scene fails the old levels are kept, so a failing scene must stay as it was.
+    fn reload(&mut self, _levels: &Levels) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the scene is removed from the stack or the game is closed
+    fn exit(&mut self, _shared: &mut Shared) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    /// Transition waiting for the screen to fade out
+    pending: Option<Transition>,
+    /// 0 is fully visible, 1 is black
+    fade: f32,
+}
+
+impl SceneStack {
+    pub fn new(scene: Box<dyn Scene>) -> Self {
+        Self {
+            scenes: vec![scene],
+            pending: None,
+            fade: 1.0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle, shared: &mut Shared) -> Result<()> {
+        let step = rl.get_frame_time() / FADE_TIME;
+        if let Some(transition) = self.pending.take() {
+            self.fade += step;
+            if self.fade >= 1.0 {
+                self.fade = 1.0;
+                self.apply(transition, shared)?;
+            } else {
+                self.pending = Some(transition);
+            }
+            return Ok(());
+        }
+        self.fade = (self.fade - step).max(0.0);
+
+        let top = self.scenes.len().saturating_sub(1);
+        for index in self.first_visible()..self.scenes.len() {
+            let transition = self.scenes[index].update(rl, shared, index == top)?;
+            if !matches!(transition, Transition::None) {
+                return self.start(transition, shared);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        for scene in &self.scenes[self.first_visible()..] {
+            scene.draw(d, shared);
+        }
+        if self.fade > 0.0 {
+            let screen = rvec2(d.get_screen_width(), d.get_screen_height());
+            d.draw_rectangle_v(
+                Vector2::zero(),
+                screen,
+                Color::new(0, 0, 0, (self.fade * 255.0) as u8),
+            );
+        }
+    }
+
+    pub fn reload(&mut self, levels: &Levels) -> Result<()> {
+        for scene in &mut self.scenes {
+            scene.reload(levels)?;
+        }
+        Ok(())
+    }
+
+    /// Exits every scene, top to bottom
+    pub fn exit(&mut self, shared: &mut Shared) -> Result<()> {
+        while let Some(mut scene) = self.scenes.pop() {
+            scene.exit(shared)?;
+        }
+        Ok(())
+    }
+
+    /// Index of the bottom scene that is drawn, overlays show what's below them
+    fn first_visible(&self) -> usize {
+        self.scenes
+            .iter()
+            .rposition(|scene| !scene.overlay())
+            .unwrap_or(0)
+    }
+
+    /// Opening or closing an overlay happens instantly, everything else fades
+    fn start(&mut self, transition: Transition, shared: &mut Shared) -> Result<()> {
+        let instant = match &transition {
+            Transition::Push(scene) => scene.overlay(),
+            Transition::Pop | Transition::Return(_) => {
+                matches!(self.scenes.last(), Some(scene) if scene.overlay())
+            }
+            _ => false,
+        };
+        if instant {
+            self.apply(transition, shared)
+        } else {
+            self.pending = Some(transition);
+            Ok(())
+        }
+    }
+
+    fn pop(&mut self, shared: &mut Shared, message: Option<Message>) -> Result<()> {
+        if let Some(mut scene) = self.scenes.pop() {
+            scene.exit(shared)?;
+        }
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.resume(shared, message)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, transition: Transition, shared: &mut Shared) -> Result<()> {
+        match transition {
+            Transition::None => (),
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => self.pop(shared, None)?,
+            Transition::Return(message) => self.pop(shared, Some(message))?,
+            Transition::Replace(scene) => {
+                if let Some(mut scene) = self.scenes.pop() {
+                    scene.exit(shared)?;
+                }
+                self.scenes.push(scene);
+            }
+            Transition::Reset(scene) => {
+                self.exit(shared)?;
+                self.scenes.push(scene);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use super::Title;
+use crate::assets::*;
+use crate::format_time;
+use crate::input::Action;
+use crate::scene::{Scene, Shared, Transition};
+use crate::timing::Timed;
+
+/// Credits and the final time, fading in line by line
+pub struct EndScreen {
+    time: Timed<f32>,
+    /// The run ended with a death in hardcore mode instead of reaching the end
+    failed: bool,
+    timer: f32,
+}
+
+impl EndScreen {
+    pub fn new(time: Timed<f32>, failed: bool) -> Self {
+        Self {
+            time,
+            failed,
+            timer: 0.0,
+        }
+    }
+}
+
+impl Scene for EndScreen {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        self.timer += rl.get_frame_time();
+        let skip = shared.input.pressed(Action::Confirm)
+            || rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT);
+        if focused && self.timer > 6.0 && skip {
+            return Ok(Transition::Reset(Box::new(Title::new(rl))));
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        fn center_text<D: RaylibDraw>(d: &mut D, text: &str, y: i32, size: i32, color: f32) {
+            d.draw_text(
+                text,
+                400 - measure_text(text, size) / 2,
+                y,
+                size,
+                Color::new(0, 0, 0, (color * 255.0) as u8),
+            );
+        }
+
+        let timer = self.timer;
+        let timing = shared.save.settings.timing;
+        let brightness = (timer / 2.0).min(1.0);
+        d.draw_texture_ex(
+            &shared.assets.title_screen,
+            Vector2::zero(),
+            0.0,
+            3.0,
+            Color::color_from_normalized(rquat(brightness, brightness, brightness, 1.0)),
+        );
+        center_text(
+            d,
+            if self.failed {
+                "Run over!"
+            } else {
+                "Thans for playing!"
+            },
+            260,
+            50,
+            (timer - 2.0).clamp(0.0, 1.0),
+        );
+        center_text(
+            d,
+            &format!("Your time: {}", format_time(*self.time.get(timing))),
+            320,
+            50,
+            (timer - 3.0).clamp(0.0, 1.0),
+        );
+        center_text(
+            d,
+            &format!(
+                "{} {}",
+                timing.other().name(),
+                format_time(*self.time.get(timing.other()))
+            ),
+            372,
+            20,
+            (timer - 3.0).clamp(0.0, 1.0),
+        );
+        center_text(
+            d,
+            "Made for IcoJam 2023",
+            400,
+            30,
+            (timer - 4.0).clamp(0.0, 1.0),
+        );
+        center_text(
+            d,
+            "By InfiniteCoder",
+            430,
+            40,
+            (timer - 5.0).clamp(0.0, 1.0),
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::Playing;
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::format_time;
+use crate::input::Action;
+use crate::scene::{Scene, Shared, Transition};
+use crate::ui::{Button, Label};
+
+/// Picks a level to practice, showing progress from the save data
+pub struct LevelSelect {
+    selected: usize,
+    buttons: Vec<Button>,
+    back: Button,
+    names: Vec<String>,
+    screen: Vector2,
+}
+
+impl LevelSelect {
+    pub fn new(rl: &RaylibHandle, level_count: usize) -> Self {
+        Self::layout(
+            rvec2(rl.get_screen_width(), rl.get_screen_height()),
+            level_count,
+        )
+    }
+
+    fn layout(screen: Vector2, level_count: usize) -> Self {
+        let top = (screen.y - level_count as f32 * 60.0) / 2.0;
+        Self {
+            selected: 0,
+            screen,
+            buttons: (0..level_count)
+                .map(|level| Button::new(rvec2(screen.x * 0.3, top + level as f32 * 60.0)))
+                .collect(),
+            back: Button::new(rvec2(screen.x / 2.0, screen.y - 50.0)),
+            names: (0..level_count)
+                .map(|level| format!("Level {}", level + 1))
+                .collect(),
+        }
+    }
+}
+
+impl Scene for LevelSelect {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        if !focused {
+            return Ok(Transition::None);
+        }
+
+        let assets = &mut shared.assets;
+        let input = &shared.input;
+        let unlocked = shared.save.unlocked_levels.min(self.buttons.len());
+        let back_index = self.buttons.len();
+
+        // The back button is the last entry when navigating with the keyboard
+        if input.pressed(Action::Down) {
+            self.selected = if self.selected + 1 >= unlocked {
+                back_index
+            } else {
+                self.selected + 1
+            };
+        }
+        if input.pressed(Action::Up) {
+            self.selected = if self.selected == back_index {
+                unlocked.saturating_sub(1)
+            } else {
+                self.selected.saturating_sub(1)
+            };
+        }
+
+        let mut choice = None;
+        for (level, button) in self.buttons.iter_mut().enumerate() {
+            let clicked = button.update(
+                rl,
+                Label::Text(&self.names[level]),
+                self.selected == level,
+                &mut assets.audio,
+                &assets.button_hover_sound,
+                &assets.button_click_sound,
+            );
+            if clicked && level < unlocked {
+                choice = Some(level);
+            }
+        }
+        if self.back.update(
+            rl,
+            Label::Text("Back"),
+            self.selected == back_index,
+            &mut assets.audio,
+            &assets.button_hover_sound,
+            &assets.button_click_sound,
+        ) {
+            choice = Some(self.buttons.len());
+        }
+
+        if input.pressed(Action::Confirm) {
+            assets.audio.play_sound(&assets.button_click_sound);
+            choice = Some(self.selected);
+        }
+        if input.pressed(Action::Back) {
+            choice = Some(self.buttons.len());
+        }
+        Ok(match choice {
+            Some(level) if level < back_index => {
+                Transition::Replace(Box::new(Playing::new(shared, level, None)?))
+            }
+            Some(_) => Transition::Pop,
+            None => Transition::None,
+        })
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        let (assets, save) = (&shared.assets, &shared.save);
+        let screen = rvec2(d.get_screen_width(), d.get_screen_height());
+        d.draw_texture_ex(
+            &assets.title_screen,
+            Vector2::zero(),
+            0.0,
+            3.0,
+            Color::new(80, 80, 80, 255),
+        );
+
+        for (level, button) in self.buttons.iter().enumerate() {
+            let locked = level >= save.unlocked_levels;
+            let tint = if locked { Color::GRAY } else { Color::WHITE };
+            button.draw(d, Label::Text(&self.names[level]), tint);
+
+            let details = if locked {
+                "Locked".to_owned()
+            } else {
+                let record = save.levels.get(level).cloned().unwrap_or_default();
+                let mode = save.settings.forgiveness;
+                format!(
+                    "Best{}: {}   Deaths: {}",
+                    match mode {
+                        Forgiveness::Normal => String::new(),
+                        mode => format!(" ({})", mode.name()),
+                    },
+                    record
+                        .best_time(mode)
+                        .map_or_else(|| "-".to_owned(), format_time),
+                    record.deaths
+                )
+            };
+            d.draw_text(
+                &details,
+                (screen.x * 0.5) as i32,
+                button.position().y as i32 - 10,
+                20,
+                tint,
+            );
+        }
+        self.back.draw(d, Label::Text("Back"), Color::WHITE);
+    }
+
+    /// Levels might have been added or removed
+    fn reload(&mut self, levels: &Levels) -> Result<()> {
+        let level_count = levels.project.levels().len();
+        let selected = if self.selected == self.buttons.len() {
+            level_count
+        } else {
+            self.selected.min(level_count)
+        };
+        *self = Self::layout(self.screen, level_count);
+        self.selected = selected;
+        Ok(())
+    }
+}
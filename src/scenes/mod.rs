@@ -0,0 +1,13 @@
+pub mod end_screen;
+pub mod level_select;
+pub mod options;
+pub mod pause;
+pub mod playing;
+pub mod title;
+
+pub use end_screen::EndScreen;
+pub use level_select::LevelSelect;
+pub use options::Options;
+pub use pause::PauseMenu;
+pub use playing::Playing;
+pub use title::Title;
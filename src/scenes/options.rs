@@ -0,0 +1,127 @@
+use crate::assets::*;
+use crate::forgiveness::{Forgiveness, LIVES, PENALTY};
+use crate::ghost::GhostMode;
+use crate::input::Action;
+use crate::save::log_failure;
+use crate::scene::{Scene, Shared, Transition};
+use crate::timing::TimingMethod;
+use crate::ui::{dim, Menu};
+use crate::world::LEVEL_FADE;
+
+/// Respawn fades to cycle through, from instant to the same fade as between levels
+const RESPAWN_FADES: [f32; 3] = [0.0, 0.25, LEVEL_FADE];
+
+/// Settings that are stored in the save data, shown on top of the previous screen
+pub struct Options {
+    menu: Menu,
+}
+
+impl Options {
+    pub fn new(rl: &RaylibHandle) -> Self {
+        let center = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0;
+        Self {
+            menu: Menu::new(center, 6),
+        }
+    }
+
+    fn labels(shared: &Shared) -> [String; 6] {
+        let settings = &shared.save.settings;
+        [
+            format!("Music: {}", if settings.music { "On" } else { "Off" }),
+            format!(
+                "Timing: {}",
+                match settings.timing {
+                    TimingMethod::RealTime => "Real time",
+                    TimingMethod::GameTime => "Game time",
+                }
+            ),
+            format!(
+                "Ghost: {}",
+                match settings.ghost {
+                    GhostMode::Off => "Off",
+                    GhostMode::Level => "Level",
+                    GhostMode::Run => "Run",
+                }
+            ),
+            if settings.respawn_fade > 0.0 {
+                format!("Respawn fade: {:.2}s", settings.respawn_fade)
+            } else {
+                "Respawn fade: Instant".to_owned()
+            },
+            format!(
+                "Mistakes: {}",
+                match settings.forgiveness {
+                    Forgiveness::Normal => "Restart level".to_owned(),
+                    Forgiveness::Lives => format!("{} lives", LIVES),
+                    Forgiveness::Penalty => format!("+{}s", PENALTY),
+                    Forgiveness::Hardcore => "End run".to_owned(),
+                }
+            ),
+            "Back".to_owned(),
+        ]
+    }
+}
+
+impl Scene for Options {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        if !focused {
+            return Ok(Transition::None);
+        }
+
+        let labels = Self::labels(shared);
+        let labels = labels.each_ref().map(String::as_str);
+        let choice = self
+            .menu
+            .update(rl, &mut shared.assets, &shared.input, &labels);
+        let settings = &mut shared.save.settings;
+        match choice {
+            Some(0) => {
+                settings.music = !settings.music;
+                if !settings.music {
+                    shared.assets.audio.stop_sound(&shared.assets.song);
+                }
+            }
+            Some(1) => settings.timing = settings.timing.other(),
+            Some(2) => {
+                settings.ghost = match settings.ghost {
+                    GhostMode::Off => GhostMode::Level,
+                    GhostMode::Level => GhostMode::Run,
+                    GhostMode::Run => GhostMode::Off,
+                }
+            }
+            Some(3) => {
+                settings.respawn_fade = RESPAWN_FADES
+                    .into_iter()
+                    .find(|fade| *fade > settings.respawn_fade)
+                    .unwrap_or(RESPAWN_FADES[0]);
+            }
+            Some(4) => settings.forgiveness = settings.forgiveness.next(),
+            Some(_) => return Ok(Transition::Pop),
+            None => (),
+        }
+        if shared.input.pressed(Action::Back) {
+            return Ok(Transition::Pop);
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        dim(d, rvec2(d.get_screen_width(), d.get_screen_height()));
+        let labels = Self::labels(shared);
+        self.menu.draw(d, &labels.each_ref().map(String::as_str));
+    }
+
+    fn overlay(&self) -> bool {
+        true
+    }
+
+    fn exit(&mut self, shared: &mut Shared) -> Result<()> {
+        log_failure(shared.save.save());
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use super::{Options, Playing, Title};
+use crate::assets::*;
+use crate::input::{Action, Input};
+use crate::scene::{Message, Scene, Shared, Transition};
+use crate::ui::{dim, Menu};
+
+const ENTRIES: [&str; 5] = [
+    "Resume",
+    "Restart Level",
+    "Restart Run",
+    "Options",
+    "Quit to Title",
+];
+
+/// Shown on top of the game while it is paused
+pub struct PauseMenu {
+    menu: Menu,
+    input: Input,
+    /// Level the paused run started on, restarting the run goes back there
+    start_level: usize,
+}
+
+impl PauseMenu {
+    /// `input` is the game input when pausing, so the held pause button doesn't resume right away
+    pub fn new(rl: &RaylibHandle, input: Input, start_level: usize) -> Self {
+        let center = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0;
+        Self {
+            menu: Menu::new(center, ENTRIES.len()),
+            input,
+            start_level,
+        }
+    }
+}
+
+impl Scene for PauseMenu {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        if !focused {
+            return Ok(Transition::None);
+        }
+        self.input = Input::poll(rl, &shared.bindings, &self.input);
+        if self.input.pressed(Action::Pause) {
+            return Ok(Transition::Pop);
+        }
+
+        Ok(
+            match self
+                .menu
+                .update(rl, &mut shared.assets, &self.input, &ENTRIES)
+            {
+                Some(0) => Transition::Pop,
+                Some(1) => Transition::Return(Message::RestartLevel),
+                Some(2) => {
+                    Transition::Reset(Box::new(Playing::new(shared, self.start_level, None)?))
+                }
+                Some(3) => Transition::Push(Box::new(Options::new(rl))),
+                Some(_) => Transition::Reset(Box::new(Title::new(rl))),
+                None => Transition::None,
+            },
+        )
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, _shared: &Shared) {
+        let screen = rvec2(d.get_screen_width(), d.get_screen_height());
+        dim(d, screen);
+        let text = "Paused";
+        d.draw_text(
+            text,
+            (screen.x as i32 - measure_text(text, 50)) / 2,
+            screen.y as i32 / 4,
+            50,
+            Color::WHITE,
+        );
+        self.menu.draw(d, &ENTRIES);
+    }
+
+    fn overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,410 @@
+use super::{EndScreen, PauseMenu};
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::format_time;
+use crate::ghost::GhostMode;
+use crate::input::{Action, Input};
+use crate::replay::Replay;
+use crate::save::{data_dir, log_failure};
+use crate::scene::{Message, Scene, Shared, Transition};
+use crate::world::*;
+
+fn save_replay(replay: &Replay) -> Result<()> {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    replay
+        .save(data_dir().join("replays").join(format!("{}.ctcr", time)))
+        .context("Failed to save replay!")
+}
+
+/// A replay being played back instead of reading input
+struct Playback {
+    inputs: Vec<(usize, Input)>,
+    next: usize,
+    time: f32,
+    desynced: bool,
+}
+
+pub struct Playing {
+    world: World,
+    playback: Option<Playback>,
+    /// Runs started from a later level are practice and don't count towards splits
+    practice: bool,
+    golds: Vec<bool>,
+    timestep: Timestep,
+    input: Input,
+    /// Set when the pause menu closes, so the next tick unpauses the world
+    unpause: bool,
+    /// Set by the pause menu, restarts the level together with unpausing
+    restart: bool,
+    finished: bool,
+}
+
+impl Playing {
+    /// Starts a run on `start_level`, or plays back `replay` from its first level
+    pub fn new(shared: &mut Shared, start_level: usize, replay: Option<Replay>) -> Result<Self> {
+        if let Some(replay) = &replay {
+            ensure!(
+                replay.levels_hash == shared.assets.world.hash,
+                "The replay was recorded on different levels or physics!"
+            );
+        }
+        let start_level = replay.as_ref().map_or(start_level, Replay::start_level);
+        let mut world = World::load(&shared.assets.world, start_level)
+            .context("Failed to load level!")?
+            .context("Failed to find first level!")?;
+        world.respawn_fade = replay
+            .as_ref()
+            .map_or(shared.save.settings.respawn_fade, |replay| {
+                replay.respawn_fade
+            });
+        world.forgiveness = replay
+            .as_ref()
+            .map_or(shared.save.settings.forgiveness, |replay| {
+                replay.forgiveness
+            });
+        shared.use_splits(world.forgiveness);
+        let practice = start_level != 0;
+        if replay.is_none() && !practice {
+            shared.splits.start_attempt();
+        }
+
+        Ok(Self {
+            world,
+            playback: replay.map(|replay| Playback {
+                inputs: replay.inputs().collect(),
+                next: 0,
+                time: replay.time,
+                desynced: false,
+            }),
+            practice,
+            golds: Vec::new(),
+            timestep: Timestep::default(),
+            input: Input::default(),
+            unpause: false,
+            restart: false,
+            finished: false,
+        })
+    }
+
+    fn tick_input(&mut self, input: Input) -> Input {
+        let Some(playback) = &mut self.playback else {
+            return input;
+        };
+        let Some(&(level, input)) = playback.inputs.get(playback.next) else {
+            return Input::default();
+        };
+        playback.next += 1;
+        if level != self.world.level.index() && !playback.desynced {
+            eprintln!(
+                "Replay desynced: expected level {}, got {}",
+                level,
+                self.world.level.index()
+            );
+            playback.desynced = true;
+        }
+        input
+    }
+
+    fn finish(&mut self, shared: &mut Shared) {
+        self.finished = true;
+        let world = &mut self.world;
+        if let Some(playback) = &self.playback {
+            if playback.time != world.time.game {
+                eprintln!(
+                    "Replay time mismatch: recorded {}, simulated {}",
+                    format_time(playback.time),
+                    format_time(world.time.game)
+                );
+            }
+            return;
+        }
+
+        log_failure(save_replay(&world.replay));
+        if !self.practice {
+            // Ghosts race normal runs only
+            if world.forgiveness == Forgiveness::Normal {
+                log_failure(
+                    shared
+                        .best_ghosts
+                        .submit_run(std::mem::take(&mut world.ghosts.run))
+                        .context("Failed to save ghost!"),
+                );
+            }
+            shared
+                .splits
+                .finish(&world.splits, shared.save.settings.timing);
+            log_failure(
+                shared
+                    .splits
+                    .save(shared.splits_file(world.forgiveness))
+                    .context("Failed to save splits!"),
+            );
+            shared.save.finish_run(world.time, world.forgiveness);
+        }
+        log_failure(shared.save.save());
+    }
+}
+
+impl Scene for Playing {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        let assets = &mut shared.assets;
+        if shared.save.settings.music && !assets.audio.is_sound_playing(&assets.song) {
+            assets.audio.play_sound(&assets.song);
+        }
+
+        // Keep track of held keys while a menu is open, but don't feed them to the game
+        self.input = Input::poll(rl, &shared.bindings, &self.input);
+        let mut input = if focused {
+            self.input
+        } else {
+            Input::default()
+        };
+        if std::mem::take(&mut self.unpause) {
+            input.press(Action::Pause);
+        }
+        if std::mem::take(&mut self.restart) {
+            input.press(Action::Restart);
+        }
+        // Pause through the input, so replays pause at the same tick
+        if focused && !rl.is_window_focused() && self.world.state == State::Playing {
+            input.press(Action::Pause);
+        }
+
+        self.world.add_real_time(rl.get_frame_time());
+        for input in self.timestep.advance(rl.get_frame_time(), &input) {
+            let input = self.tick_input(input);
+            self.world.step(&assets.world, TICK, &input)?;
+            if matches!(self.world.state, State::Finished | State::Failed) {
+                break;
+            }
+        }
+
+        let recording = self.playback.is_none();
+        for event in self.world.events.drain(..) {
+            match event {
+                Event::Jump => assets.audio.play_sound(&assets.jump_sound),
+                Event::Number => assets.audio.play_sound(&assets.number_sound),
+                // Mistakes that end the attempt come with a game over as well
+                Event::Mistake => (),
+                Event::GameOver => {
+                    assets.audio.play_sound(&assets.game_over_sound);
+                    if recording {
+                        shared.save.level(self.world.level.index()).deaths += 1;
+                    }
+                }
+                Event::NextLevel => assets.audio.play_sound(&assets.next_level_sound),
+                Event::LevelComplete { level, time } => {
+                    if recording {
+                        shared
+                            .save
+                            .complete_level(level, time, self.world.forgiveness);
+                        log_failure(shared.save.save());
+                    }
+                }
+            }
+        }
+        if recording && !self.practice {
+            while self.golds.len() < self.world.splits.len() {
+                let times = &self.world.splits[..=self.golds.len()];
+                self.golds
+                    .push(shared.splits.split(times, shared.save.settings.timing));
+            }
+        }
+        if recording && self.world.forgiveness == Forgiveness::Normal {
+            for attempt in self.world.ghosts.finished.drain(..) {
+                log_failure(
+                    shared
+                        .best_ghosts
+                        .submit_level(attempt)
+                        .context("Failed to save ghost!"),
+                );
+            }
+        }
+
+        if self.world.state == State::Finished {
+            self.finish(shared);
+            return Ok(Transition::Replace(Box::new(EndScreen::new(
+                self.world.time,
+                false,
+            ))));
+        }
+        // Leaving the scene keeps the replay and splits like quitting early does
+        if self.world.state == State::Failed {
+            return Ok(Transition::Replace(Box::new(EndScreen::new(
+                self.world.time,
+                true,
+            ))));
+        }
+        if recording && focused && self.world.state == State::Paused {
+            return Ok(Transition::Push(Box::new(PauseMenu::new(
+                rl,
+                self.input,
+                self.world.replay.start_level(),
+            ))));
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        let World {
+            level,
+            player,
+            state,
+            time,
+            ghosts,
+            forgiveness,
+            lives,
+            attempt_time,
+            ..
+        } = &self.world;
+        let (assets, best_ghosts) = (&shared.assets, &shared.best_ghosts);
+        let timing = shared.save.settings.timing;
+        let alpha = self.timestep.alpha();
+
+        let center = rvec2(d.get_screen_width(), d.get_screen_height()) / 2.0;
+        // d.clear_background(Color::new(86, 86, 86, 255));
+        d.draw_texture_ex(&assets.background, Vector2::zero(), 0.0, 3.0, Color::WHITE);
+        {
+            let mut d = d.begin_mode2D(player.camera(level));
+            level.draw(assets, &mut d, alpha);
+            if state.player_visible() {
+                let ghost = match shared.save.settings.ghost {
+                    GhostMode::Off => None,
+                    GhostMode::Level => best_ghosts
+                        .levels
+                        .get(level.index())
+                        .and_then(Option::as_ref)
+                        .zip(ghosts.attempt.frames.len().checked_sub(1)),
+                    GhostMode::Run => best_ghosts
+                        .run
+                        .as_ref()
+                        .zip(ghosts.run.frames.len().checked_sub(1)),
+                };
+                if let Some((ghost_level, position, frame)) =
+                    ghost.and_then(|(ghost, tick)| ghost.at(tick, alpha))
+                {
+                    if ghost_level == level.index() {
+                        player.draw_frame(
+                            assets,
+                            &mut d,
+                            position,
+                            frame,
+                            Color::new(255, 255, 255, 96),
+                        );
+                    }
+                }
+                // Blink while wrong numbers are ignored after a mistake
+                let tint = if player.recovering() && (attempt_time * 10.0) as i32 % 2 == 0 {
+                    Color::new(255, 255, 255, 96)
+                } else {
+                    Color::WHITE
+                };
+                player.draw(assets, &mut d, alpha, tint);
+            }
+            for particle in &level.particles {
+                particle.draw(&mut d, alpha);
+            }
+        }
+
+        for overlay in &level.overlays {
+            let font_size = (overlay.time.powi(3) * center.y * 14.0) as i32;
+            let position = center - rvec2(measure_text(overlay.text(), font_size), font_size) / 2.0;
+            d.draw_text(
+                overlay.text(),
+                position.x as _,
+                position.y as _,
+                font_size,
+                Color::WHITE,
+            );
+        }
+
+        if let State::LevelTransition { timer, fade, .. } = state {
+            if *fade > 0.0 {
+                d.draw_rectangle_v(
+                    Vector2::zero(),
+                    center * 2.0,
+                    Color::new(0, 0, 0, 255 - (timer.abs().min(*fade) / fade * 255.0) as u8),
+                )
+            }
+        }
+
+        let mut text = format!("{} {}", timing.name(), format_time(*time.get(timing)));
+        if *state == State::Paused {
+            text.push_str(" (paused)");
+        }
+        d.draw_text(&text, 10, 10, 20, Color::WHITE);
+        d.draw_text(
+            &format!(
+                "{} {}",
+                timing.other().name(),
+                format_time(*time.get(timing.other()))
+            ),
+            10,
+            34,
+            15,
+            Color::LIGHTGRAY,
+        );
+        for (index, track) in level.tracks.iter().enumerate() {
+            let rule = track.sequence.rule().name();
+            let goal = match track.sequence.next() {
+                Some(number) => format!("{}: next {}", rule, number),
+                None => format!("{}: door open", rule),
+            };
+            d.draw_text(&goal, 10, 53 + index as i32 * 18, 15, track.color());
+        }
+        let mode = match forgiveness {
+            Forgiveness::Normal => None,
+            Forgiveness::Lives if *lives == 0 => Some("Out of lives, starting over".to_owned()),
+            Forgiveness::Lives => Some(format!("Lives: {}", lives)),
+            mode => Some(mode.name().to_owned()),
+        };
+        if let Some(mode) = mode {
+            let y = 53 + level.tracks.len() as i32 * 18;
+            d.draw_text(&mode, 10, y, 15, Color::LIGHTGRAY);
+        }
+        if !self.practice {
+            shared.splits.draw(
+                d,
+                &self.world.splits,
+                &self.golds,
+                timing,
+                center.x as i32 * 2 - 10,
+            );
+        }
+    }
+
+    fn resume(&mut self, _shared: &mut Shared, message: Option<Message>) -> Result<()> {
+        self.unpause = self.world.state == State::Paused;
+        self.restart = message == Some(Message::RestartLevel);
+        Ok(())
+    }
+
+    fn reload(&mut self, levels: &Levels) -> Result<()> {
+        self.world.reload(levels).context("Failed to reload level!")
+    }
+
+    /// Keeps the replay and progress of a run that was left early
+    fn exit(&mut self, shared: &mut Shared) -> Result<()> {
+        if self.playback.is_some() || self.finished {
+            return Ok(());
+        }
+        log_failure(save_replay(&self.world.replay));
+        if !self.practice {
+            log_failure(
+                shared
+                    .splits
+                    .save(shared.splits_file(self.world.forgiveness))
+                    .context("Failed to save splits!"),
+            );
+        }
+        log_failure(shared.save.save());
+        Ok(())
+    }
+}
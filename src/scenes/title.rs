@@ -0,0 +1,118 @@
+use super::{LevelSelect, Options, Playing};
+use crate::assets::*;
+use crate::save::log_failure;
+use crate::scene::{Scene, Shared, Transition};
+use crate::ui::{Button, Label};
+
+pub struct Title {
+    play: Button,
+    levels: Button,
+    options: Button,
+    music: Button,
+}
+
+impl Title {
+    pub fn new(rl: &RaylibHandle) -> Self {
+        let center = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0 - rvec2(0, 80);
+        Self {
+            play: Button::new(center),
+            levels: Button::new(center + rvec2(0, 75)),
+            options: Button::new(center + rvec2(0, 120)),
+            music: Button::new(center + rvec2(0, 195)),
+        }
+    }
+}
+
+impl Scene for Title {
+    fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        shared: &mut Shared,
+        focused: bool,
+    ) -> Result<Transition> {
+        if !focused {
+            return Ok(Transition::None);
+        }
+
+        let assets = &mut shared.assets;
+        if self.play.update(
+            rl,
+            Label::Texture(&assets.button_play),
+            false,
+            &mut assets.audio,
+            &assets.button_hover_sound,
+            &assets.button_click_sound,
+        ) {
+            return Ok(Transition::Replace(Box::new(Playing::new(
+                shared, 0, None,
+            )?)));
+        }
+
+        if self.levels.update(
+            rl,
+            Label::Text("Levels"),
+            false,
+            &mut assets.audio,
+            &assets.button_hover_sound,
+            &assets.button_click_sound,
+        ) {
+            let level_count = assets.world.project.levels().len();
+            return Ok(Transition::Push(Box::new(LevelSelect::new(
+                rl,
+                level_count,
+            ))));
+        }
+
+        if self.options.update(
+            rl,
+            Label::Text("Options"),
+            false,
+            &mut assets.audio,
+            &assets.button_hover_sound,
+            &assets.button_click_sound,
+        ) {
+            return Ok(Transition::Push(Box::new(Options::new(rl))));
+        }
+
+        let music = shared.save.settings.music;
+        if self.music.update(
+            rl,
+            Label::Texture(if music {
+                &assets.button_nomusic
+            } else {
+                &assets.button_music
+            }),
+            false,
+            &mut assets.audio,
+            &assets.button_hover_sound,
+            &assets.button_click_sound,
+        ) {
+            shared.save.settings.music = !music;
+            log_failure(shared.save.save());
+        }
+        Ok(Transition::None)
+    }
+
+    fn draw(&self, d: &mut RaylibDrawHandle, shared: &Shared) {
+        let assets = &shared.assets;
+        d.draw_texture_ex(
+            &assets.title_screen,
+            Vector2::zero(),
+            0.0,
+            3.0,
+            Color::WHITE,
+        );
+
+        self.play
+            .draw(d, Label::Texture(&assets.button_play), Color::WHITE);
+        self.levels.draw(d, Label::Text("Levels"), Color::WHITE);
+        self.options.draw(d, Label::Text("Options"), Color::WHITE);
+        let music_asset = if shared.save.settings.music {
+            &assets.button_nomusic
+        } else {
+            &assets.button_music
+        };
+        self.music
+            .draw(d, Label::Texture(music_asset), Color::WHITE);
+    }
+}
@@ -0,0 +1,249 @@
+use std::rc::Rc;
+
+use ldtk_easy::entity::Field;
+
+use crate::assets::*;
+
+/// Field of levels and doors the rule is read from, levels without one count down
+pub const SEQUENCE_FIELD: &str = "Sequence";
+/// Most numbers a sequence can have, far more than fit in a level
+pub const MAX_ORDER: usize = 256;
+
+/// Which numbers of a level have to be collected, and in which order
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequenceRule {
+    /// From the target down to the given end, 1 by default
+    CountDown(i32),
+    /// From the given start up to the target, 1 by default
+    CountUp(i32),
+    /// Even numbers from the target down to the given end, 1 by default
+    Even(i32),
+    /// From the target down to the given end (1 by default), skipping by the given step
+    Step(i32, i32),
+    /// Primes from the target down, there are none below 2
+    Primes,
+    /// Exactly these numbers, the target doesn't matter
+    List(Vec<i32>),
+}
+
+impl SequenceRule {
+    /// Reads a rule like `CountUp`, `CountDown 0`, `Even -4`, `Step 2 0` or `List 3, -1, 4`,
+    /// empty text counts down
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut words = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty());
+        let name = words.next().unwrap_or("CountDown");
+        let numbers = words
+            .map(|word| {
+                word.parse::<i32>()
+                    .with_context(|| format!("'{}' is not a number!", word))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(match (name, numbers.as_slice()) {
+            ("CountDown", []) => Self::CountDown(1),
+            ("CountDown", [end]) => Self::CountDown(*end),
+            ("CountUp", []) => Self::CountUp(1),
+            ("CountUp", [start]) => Self::CountUp(*start),
+            ("Even", []) => Self::Even(1),
+            ("Even", [end]) => Self::Even(*end),
+            ("Primes", []) => Self::Primes,
+            ("Step", [step]) if *step > 0 => Self::Step(*step, 1),
+            ("Step", [step, end]) if *step > 0 => Self::Step(*step, *end),
+            ("List", list) if !list.is_empty() => Self::List(list.to_vec()),
+            ("CountDown" | "CountUp" | "Even" | "Primes" | "Step" | "List", _) => {
+                bail!("Unexpected numbers for sequence rule '{}'!", name)
+            }
+            (other, _) => bail!("Unknown sequence rule '{}'!", other),
+        })
+    }
+
+    /// The rule in a [`SEQUENCE_FIELD`] of a level or door, if it has one
+    pub fn from_field(field: Option<Field>) -> Result<Option<Self>> {
+        match field {
+            None | Some(Field::Null) => Ok(None),
+            Some(Field::String { value }) => Self::parse(&value).map(Some),
+            _ => bail!("Field '{}' is of unexpected type!", SEQUENCE_FIELD),
+        }
+    }
+
+    /// The numbers to collect in order, for a level with the given target number.
+    /// Fails if there are more than [`MAX_ORDER`] of them.
+    pub fn order(&self, target: i32) -> Result<Vec<i32>> {
+        let down = |end: i32| (end..=target).rev();
+        let numbers: Box<dyn Iterator<Item = i32>> = match self {
+            Self::CountDown(end) => Box::new(down(*end)),
+            Self::CountUp(start) => Box::new(*start..=target),
+            Self::Even(end) => Box::new(down(*end).filter(|number| number % 2 == 0)),
+            Self::Step(step, end) => Box::new(down(*end).step_by(*step as usize)),
+            Self::Primes => Box::new(down(2).filter(|&number| is_prime(number))),
+            Self::List(list) => Box::new(list.iter().copied()),
+        };
+        // Ranges can span billions of numbers, so stop right after the limit
+        let order: Vec<_> = numbers.take(MAX_ORDER + 1).collect();
+        if order.len() > MAX_ORDER {
+            bail!(
+                "Sequence to {} has more than {} numbers!",
+                target,
+                MAX_ORDER
+            );
+        }
+        Ok(order)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::CountDown(_) => "Count down".to_owned(),
+            Self::CountUp(_) => "Count up".to_owned(),
+            Self::Even(_) => "Even numbers".to_owned(),
+            Self::Step(step, _) => format!("Count down by {}", step),
+            Self::Primes => "Primes".to_owned(),
+            Self::List(_) => "In order".to_owned(),
+        }
+    }
+}
+
+/// Trial division up to the square root, `number` is at least 2
+fn is_prime(number: i32) -> bool {
+    (2..)
+        .take_while(|&other| other <= number / other)
+        .all(|other| number % other != 0)
+}
+
+/// How far a level is through its rule, the door opens once every number is collected
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    /// Shared between copies, like the rule they never change after loading
+    rule: Rc<SequenceRule>,
+    order: Rc<[i32]>,
+    collected: usize,
+}
+
+impl Sequence {
+    pub fn new(rule: SequenceRule, target: i32) -> Result<Self> {
+        Ok(Self {
+            order: rule.order(target)?.into(),
+            rule: rule.into(),
+            collected: 0,
+        })
+    }
+
+    /// The number that has to be collected next, `None` once the door opens
+    pub fn next(&self) -> Option<i32> {
+        self.order.get(self.collected).copied()
+    }
+
+    pub fn advance(&mut self) {
+        self.collected = (self.collected + 1).min(self.order.len());
+    }
+
+    pub fn finished(&self) -> bool {
+        self.collected == self.order.len()
+    }
+
+    /// How many numbers were collected so far
+    pub fn collected(&self) -> usize {
+        self.collected
+    }
+
+    pub fn order(&self) -> &[i32] {
+        &self.order
+    }
+
+    pub fn rule(&self) -> &SequenceRule {
+        &self.rule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(rule: &str, target: i32) -> Vec<i32> {
+        SequenceRule::parse(rule).unwrap().order(target).unwrap()
+    }
+
+    #[test]
+    fn count_down() {
+        assert_eq!(order("", 3), [3, 2, 1]);
+        assert_eq!(order("CountDown 0", 2), [2, 1, 0]);
+        assert_eq!(order("CountDown -2", 1), [1, 0, -1, -2]);
+        assert!(order("CountDown", 0).is_empty());
+    }
+
+    #[test]
+    fn count_up() {
+        assert_eq!(order("CountUp", 3), [1, 2, 3]);
+        assert_eq!(order("CountUp -1", 1), [-1, 0, 1]);
+        assert!(order("CountUp 4", 3).is_empty());
+    }
+
+    #[test]
+    fn even() {
+        assert_eq!(order("Even", 6), [6, 4, 2]);
+        assert_eq!(order("Even -4", 3), [2, 0, -2, -4]);
+        assert!(order("Even", 1).is_empty());
+    }
+
+    #[test]
+    fn step() {
+        assert_eq!(order("Step 3", 10), [10, 7, 4, 1]);
+        assert_eq!(order("Step 2 -3", 3), [3, 1, -1, -3]);
+        assert_eq!(order("Step 1 0", 2), [2, 1, 0]);
+    }
+
+    #[test]
+    fn primes() {
+        assert_eq!(order("Primes", 12), [11, 7, 5, 3, 2]);
+        assert!(order("Primes", 1).is_empty());
+        assert!(order("Primes", -7).is_empty());
+    }
+
+    #[test]
+    fn large_primes() {
+        assert!(is_prime(104_729));
+        assert!(!is_prime(104_729 * 3));
+        assert!(!is_prime(46_337 * 46_337));
+        assert!(is_prime(i32::MAX));
+        assert!(!is_prime(i32::MAX - 1));
+        assert_eq!(order("Primes", 1000)[..2], [997, 991]);
+    }
+
+    #[test]
+    fn long_sequences_are_rejected() {
+        let rule = SequenceRule::parse("CountDown").unwrap();
+        assert_eq!(rule.order(MAX_ORDER as i32).unwrap().len(), MAX_ORDER);
+        assert!(rule.order(MAX_ORDER as i32 + 1).is_err());
+        assert!(rule.order(i32::MAX).is_err());
+        assert!(SequenceRule::parse("CountUp")
+            .unwrap()
+            .order(i32::MAX)
+            .is_err());
+        assert!(SequenceRule::parse("CountDown -2147483648")
+            .unwrap()
+            .order(i32::MAX)
+            .is_err());
+        assert!(Sequence::new(rule, i32::MAX).is_err());
+    }
+
+    #[test]
+    fn list() {
+        assert_eq!(order("List 3, -1, 4", 10), [3, -1, 4]);
+        assert_eq!(order("List 0", -5), [0]);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for text in [
+            "Step",
+            "Step 0",
+            "Step -1",
+            "Even 1 2",
+            "List",
+            "CountDown x",
+            "Odd",
+        ] {
+            assert!(SequenceRule::parse(text).is_err(), "{}", text);
+        }
+    }
+}
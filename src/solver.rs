@@ -0,0 +1,440 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::rc::Rc;
+
+use crate::assets::*;
+use crate::forgiveness::Forgiveness;
+use crate::input::{Action, Input};
+use crate::level::{Level, Number, Track, SPIDER_CYCLE};
+use crate::player::Player;
+use crate::replay::{Replay, Segment};
+use crate::world::{Event, LEVEL_FADE, TICK};
+
+/// Inputs are held for this many ticks before the search branches again
+const STEP_TICKS: u32 = 6;
+/// Give up after expanding this many states
+const MAX_EXPANSIONS: usize = 2_000_000;
+/// Trades optimality for search time, higher finds solutions faster but they are slower.
+/// Above 1 the search isn't admissible, which is why solutions are reported as upper bounds.
+const HEURISTIC_WEIGHT: u32 = 3;
+/// Ticks per spider phase in [`Key`], and how many phases there are before spiders repeat
+const SPIDER_PHASE_TICKS: u32 = 30;
+const SPIDER_PHASES: u32 = (SPIDER_CYCLE / TICK + 0.5) as u32 / SPIDER_PHASE_TICKS;
+
+/// Held actions the search tries: every direction, with and without jumping and grabbing
+fn choices() -> Vec<u16> {
+    let mut choices = Vec::new();
+    for direction in [None, Some(Action::Left), Some(Action::Right)] {
+        for jump in [false, true] {
+            for grab in [false, true] {
+                if grab && direction.is_none() {
+                    continue;
+                }
+                let mut down = direction.map_or(0, Action::mask);
+                if jump {
+                    down |= Action::Jump.mask();
+                }
+                if grab {
+                    down |= Action::Grab.mask();
+                }
+                choices.push(down);
+            }
+        }
+    }
+    choices
+}
+
+pub struct Solution {
+    /// Ticks the solution takes. The search is greedy, so this is an upper bound of the
+    /// fastest way through the level, not necessarily the fastest itself.
+    pub ticks: u32,
+    pub replay: Replay,
+}
+
+/// What changes while playing a level, the rest of the level is the same for every node
+struct Node {
+    /// Shared with the parent while nothing the simulation depends on changed
+    numbers: Rc<Vec<Number>>,
+    tracks: Rc<Vec<Track>>,
+    player: Player,
+    input: Input,
+    ticks: u32,
+    parent: Option<usize>,
+}
+
+/// Rough state of a node, states that look the same are only explored once
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    position: (i32, i32),
+    velocity: (i32, i32),
+    player: (u8, bool),
+    /// Numbers collected of every track
+    collected: Vec<usize>,
+    /// Doors keep rising after the last number, waiting for them is progress too
+    doors: Vec<i32>,
+    /// Spiders move on a timer, so time within their cycle matters in levels that have them
+    spider_phase: u32,
+}
+
+impl Node {
+    /// Puts the numbers and doors of this node into `level`
+    fn restore(&self, level: &mut Level) {
+        level.numbers.clone_from(&self.numbers);
+        level.tracks.clone_from(&self.tracks);
+    }
+
+    /// Keeps the numbers and doors `level` holds after advancing from `parent`. Without spiders
+    /// numbers only move their bob, which nothing but drawing looks at, so they're shared
+    /// until one is collected. Doors are shared until one moves.
+    fn store(&mut self, level: &Level, parent: &Node, spiders: bool) {
+        self.numbers = if !spiders && level.numbers.len() == parent.numbers.len() {
+            parent.numbers.clone()
+        } else {
+            Rc::new(level.numbers.clone())
+        };
+        let same = |a: &Track, b: &Track| {
+            a.sequence.collected() == b.sequence.collected() && a.door().y == b.door().y
+        };
+        self.tracks = if level
+            .tracks
+            .iter()
+            .zip(parent.tracks.iter())
+            .all(|(a, b)| same(a, b))
+        {
+            parent.tracks.clone()
+        } else {
+            Rc::new(level.tracks.clone())
+        };
+    }
+
+    /// `level` has to hold the state of this node
+    fn key(&self, level: &Level, spiders: bool) -> Key {
+        let bucket = |value: f32, size: f32| (value / size).floor() as i32;
+        let (position, velocity) = (self.player.position(), self.player.velocity());
+        Key {
+            position: (bucket(position.x, 4.0), bucket(position.y, 4.0)),
+            velocity: (bucket(velocity.x, 25.0), bucket(velocity.y, 25.0)),
+            player: (self.player.jumps(), self.player.holding_to_wall()),
+            collected: level
+                .tracks
+                .iter()
+                .map(|track| track.sequence.collected())
+                .collect(),
+            doors: level
+                .tracks
+                .iter()
+                .map(|track| bucket(track.door().y, 4.0))
+                .collect(),
+            spider_phase: if spiders {
+                self.ticks / SPIDER_PHASE_TICKS % SPIDER_PHASES
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Estimate of the ticks left, following the distance fields through the remaining numbers
+    fn heuristic(&self, level: &Level, guide: &Guide) -> u32 {
+        // Roughly the fastest the player moves, to turn distances into ticks
+        let physics = &level.physics;
+        let speed = physics
+            .jump_speed
+            .max(physics.wall_jump_speed)
+            .max(physics.run_speed);
+        let ticks = |cells: u32| (cells as f32 * 16.0 / speed / TICK) as u32;
+        let center = self.player.center();
+        // Doors only open after their last number, so leaving can't be faster than that
+        let tracks = &level.tracks;
+        let door = tracks
+            .iter()
+            .map(|track| (track.door_time_left() / TICK) as u32)
+            .max()
+            .unwrap_or(0);
+        let exit = ticks(guide.distance(&guide.exit, center)).max(door);
+        // Tracks can be collected in any interleaving, so the longest one is the best guess
+        tracks
+            .iter()
+            .zip(&guide.routes)
+            .filter_map(|(track, route)| {
+                let step = track.sequence.collected();
+                let numbers = guide
+                    .distance(route.fields.get(step)?, center)
+                    .saturating_add(route.chain[step]);
+                Some(ticks(numbers).saturating_add(ticks(route.exit).max(door)))
+            })
+            .max()
+            .unwrap_or(exit)
+    }
+
+    /// Runs the same per-tick updates as `World::step` while playing, on the state in `level`.
+    /// Returns `None` if the player died, or the tick the level was left on.
+    fn advance(&mut self, level: &mut Level, down: u16) -> Option<Option<u32>> {
+        let mut events = Vec::new();
+        for tick in 0..STEP_TICKS {
+            self.input = self.input.next(down);
+            self.player.save_position();
+            level.save_positions();
+            level.update(TICK);
+            self.player.update(TICK, &self.input, level, &mut events);
+            // Nothing in the simulation depends on these, so don't keep them around
+            level.particles.clear();
+            level.overlays.clear();
+
+            // Solutions are for normal mode, where every mistake ends the attempt
+            if events.contains(&Event::GameOver) || events.contains(&Event::Mistake) {
+                return None;
+            }
+            if self.player.position().x >= level.size().x {
+                return Some(Some(self.ticks + tick + 1));
+            }
+        }
+        self.ticks += STEP_TICKS;
+        Some(None)
+    }
+}
+
+/// Searches for a fast way through level `index`, collecting the numbers in order
+pub fn solve(levels: &Levels, index: usize) -> Result<Option<Solution>> {
+    // Only one copy of the level, the nodes swap their state in and out of it
+    let (mut level, player) = Level::load(levels, index)?.context("Level doesn't exist!")?;
+    let spiders = level.numbers.iter().any(|number| number.is_spider());
+    let choices = choices();
+    let guide = Guide::new(&level);
+
+    let mut nodes = vec![Node {
+        numbers: Rc::new(level.numbers.clone()),
+        tracks: Rc::new(level.tracks.clone()),
+        player,
+        input: Input::default(),
+        ticks: 0,
+        parent: None,
+    }];
+    let mut downs = vec![0];
+    let mut queue =
+        BinaryHeap::from([(Reverse(nodes[0].heuristic(&level, &guide)), Reverse(0), 0)]);
+    let mut visited = HashSet::from([nodes[0].key(&level, spiders)]);
+
+    let mut expansions = 0;
+    while let Some((_, _, index)) = queue.pop() {
+        expansions += 1;
+        if expansions > MAX_EXPANSIONS {
+            break;
+        }
+
+        for &down in &choices {
+            let node = &nodes[index];
+            node.restore(&mut level);
+            let mut next = Node {
+                numbers: Rc::default(),
+                tracks: Rc::default(),
+                player: node.player.clone(),
+                input: node.input,
+                ticks: node.ticks,
+                parent: Some(index),
+            };
+            // Dead ends are dropped right away
+            let Some(exit) = next.advance(&mut level, down) else {
+                continue;
+            };
+            if let Some(ticks) = exit {
+                nodes.push(next);
+                downs.push(down);
+                return Ok(Some(solution(
+                    &nodes,
+                    &downs,
+                    nodes.len() - 1,
+                    levels,
+                    level.index(),
+                    ticks,
+                )));
+            }
+            if visited.insert(next.key(&level, spiders)) {
+                let cost = next.ticks + next.heuristic(&level, &guide) * HEURISTIC_WEIGHT;
+                // Only states worth coming back to keep the numbers and doors
+                next.store(&level, &nodes[index], spiders);
+                queue.push((Reverse(cost), Reverse(next.ticks), nodes.len()));
+                nodes.push(next);
+                downs.push(down);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Tile distances to each number and to the exit, ignoring gravity
+struct Guide {
+    width: usize,
+    height: usize,
+    /// Distance field towards the exit
+    exit: Vec<u32>,
+    /// One for every track of the level
+    routes: Vec<Route>,
+}
+
+/// The way through the numbers of a track
+struct Route {
+    /// Distance field towards the `n`th number of the track's sequence
+    fields: Vec<Vec<u32>>,
+    /// Distance from the `n`th number through the rest of the sequence
+    chain: Vec<u32>,
+    /// Distance from the last number to the exit
+    exit: u32,
+}
+
+impl Guide {
+    fn new(level: &Level) -> Self {
+        let size = level.size() / 16.0;
+        let (width, height) = (size.x as usize, size.y as usize);
+        let passable: Vec<bool> = (0..width * height)
+            .map(|index| {
+                let tile = level.tile(rvec2((index % width) as f32, (index / width) as f32));
+                !tile.solid && !tile.damage
+            })
+            .collect();
+
+        let cell = |position: Vector2| {
+            let (x, y) = ((position.x / 16.0) as usize, (position.y / 16.0) as usize);
+            x.min(width - 1) + y.min(height - 1) * width
+        };
+        let targets = |track: usize, number: i32| -> Vec<usize> {
+            // Spiders can be caught anywhere along their thread
+            let mut cells = Vec::new();
+            for other in level
+                .numbers
+                .iter()
+                .filter(|other| other.track() == track && other.number() == number)
+            {
+                let (start, end) = other.path();
+                let steps = (start.distance_to(end) / 8.0).ceil() as usize;
+                for step in 0..=steps {
+                    let position = start.lerp(end, step as f32 / steps.max(1) as f32);
+                    cells.push(cell(position + 8.0));
+                }
+            }
+            cells
+        };
+        let exit = flood(
+            width,
+            height,
+            &passable,
+            &(0..height)
+                .map(|y| width - 1 + y * width)
+                .collect::<Vec<_>>(),
+        );
+
+        let routes = level
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(track, Track { sequence, .. })| {
+                let order = sequence.order();
+                let fields: Vec<_> = order
+                    .iter()
+                    .map(|&number| flood(width, height, &passable, &targets(track, number)))
+                    .collect();
+                // Shortest way from the number of step `from` to anywhere in `field`
+                let leg = |from: usize, field: &[u32]| {
+                    targets(track, order[from])
+                        .into_iter()
+                        .map(|start| field[start])
+                        .min()
+                        .unwrap_or(u32::MAX)
+                };
+                let mut chain = vec![0; order.len()];
+                for step in (1..order.len()).rev() {
+                    chain[step - 1] = leg(step - 1, &fields[step]).saturating_add(chain[step]);
+                }
+                Route {
+                    exit: match order.len() {
+                        0 => 0,
+                        len => leg(len - 1, &exit),
+                    },
+                    fields,
+                    chain,
+                }
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            exit,
+            routes,
+        }
+    }
+
+    fn distance(&self, field: &[u32], position: Vector2) -> u32 {
+        let (x, y) = ((position.x / 16.0) as usize, (position.y / 16.0) as usize);
+        let index = x.min(self.width - 1) + y.min(self.height - 1) * self.width;
+        field[index]
+    }
+}
+
+/// Breadth first distances from `starts` through passable cells
+fn flood(width: usize, height: usize, passable: &[bool], starts: &[usize]) -> Vec<u32> {
+    let mut distances = vec![u32::MAX; width * height];
+    let mut queue = VecDeque::new();
+    for &start in starts {
+        distances[start] = 0;
+        queue.push_back(start);
+    }
+    while let Some(index) = queue.pop_front() {
+        let (x, y) = (index % width, index / width);
+        let neighbours = [
+            (x > 0).then(|| index - 1),
+            (x + 1 < width).then_some(index + 1),
+            (y > 0).then(|| index - width),
+            (y + 1 < height).then_some(index + width),
+        ];
+        for next in neighbours.into_iter().flatten() {
+            if passable[next] && distances[next] == u32::MAX {
+                distances[next] = distances[index] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+/// Walks back from the goal and turns the held actions into a replay
+fn solution(
+    nodes: &[Node],
+    downs: &[u16],
+    goal: usize,
+    levels: &Levels,
+    level: usize,
+    ticks: u32,
+) -> Solution {
+    let mut path = Vec::new();
+    let mut index = Some(goal);
+    while let Some(node) = index {
+        if nodes[node].parent.is_some() {
+            path.push(downs[node]);
+        }
+        index = nodes[node].parent;
+    }
+
+    let mut input = Input::default();
+    let mut inputs = Vec::new();
+    for down in path.into_iter().rev() {
+        for _ in 0..STEP_TICKS {
+            input = input.next(down);
+            inputs.push(input);
+        }
+    }
+    inputs.truncate(ticks as usize);
+
+    Solution {
+        ticks,
+        replay: Replay {
+            segments: vec![Segment {
+                level,
+                ticks: inputs,
+            }],
+            time: ticks as f32 * TICK,
+            respawn_fade: LEVEL_FADE,
+            forgiveness: Forgiveness::Normal,
+            levels_hash: levels.hash,
+        },
+    }
+}
@@ -0,0 +1,585 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::*;
+use crate::format_time;
+use crate::save::write_atomic;
+use crate::timing::{Timed, TimingMethod};
+
+/// One split per level, stored as a LiveSplit `.lss` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub name: String,
+    /// Run time at the end of this level in the personal best
+    pub personal_best: Timed<Option<f32>>,
+    /// Fastest this level has ever been done (the gold split)
+    pub best_segment: Timed<Option<f32>>,
+}
+
+/// A started run, with its final times if it was finished
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    pub id: u32,
+    pub time: Timed<Option<f32>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Splits {
+    pub attempts: u32,
+    pub history: Vec<Attempt>,
+    pub segments: Vec<Segment>,
+}
+
+impl Splits {
+    pub fn new(level_count: usize) -> Self {
+        let mut splits = Self {
+            attempts: 0,
+            history: Vec::new(),
+            segments: Vec::new(),
+        };
+        splits.resize(level_count);
+        splits
+    }
+
+    /// Make sure there is exactly one segment per level
+    pub fn resize(&mut self, level_count: usize) {
+        while self.segments.len() < level_count {
+            self.segments.push(Segment {
+                name: format!("Level {}", self.segments.len() + 1),
+                personal_best: Timed::default(),
+                best_segment: Timed::default(),
+            });
+        }
+        self.segments.truncate(level_count);
+    }
+
+    pub fn sum_of_best(&self, method: TimingMethod) -> Option<f32> {
+        self.segments
+            .iter()
+            .map(|segment| *segment.best_segment.get(method))
+            .sum()
+    }
+
+    /// Length of split `index` of a run with the given split times
+    pub fn segment_time(times: &[Timed<f32>], index: usize) -> Timed<f32> {
+        let previous = index
+            .checked_sub(1)
+            .map_or(Timed::default(), |index| times[index]);
+        Timed {
+            real: times[index].real - previous.real,
+            game: times[index].game - previous.game,
+        }
+    }
+
+    /// Compare a new split against the personal best, updating the gold splits.
+    /// Returns whether it was a gold split with the given timing method.
+    pub fn split(&mut self, times: &[Timed<f32>], method: TimingMethod) -> bool {
+        let index = times.len() - 1;
+        let time = Self::segment_time(times, index);
+        let Some(segment) = self.segments.get_mut(index) else {
+            return false;
+        };
+
+        let mut gold = false;
+        for current in [method, method.other()] {
+            let time = *time.get(current);
+            let best = segment.best_segment.get_mut(current);
+            if !matches!(best, Some(best) if *best <= time) {
+                *best = Some(time);
+                gold |= current == method;
+            }
+        }
+        gold
+    }
+
+    pub fn start_attempt(&mut self) {
+        self.attempts += 1;
+        self.history.push(Attempt {
+            id: self.attempts,
+            time: Timed::default(),
+        });
+    }
+
+    /// Record the final times of the current attempt and replace the personal best
+    /// if `times` is a faster complete run. Returns whether it was a new personal best.
+    pub fn finish(&mut self, times: &[Timed<f32>], method: TimingMethod) -> bool {
+        if times.len() != self.segments.len() {
+            return false;
+        }
+        let time = times[times.len() - 1];
+        if let Some(attempt) = self.history.last_mut() {
+            attempt.time = time.map(Some);
+        }
+
+        let personal_best = self
+            .segments
+            .last()
+            .and_then(|segment| *segment.personal_best.get(method));
+        if personal_best.is_some_and(|best| best <= *time.get(method)) {
+            return false;
+        }
+        for (segment, time) in self.segments.iter_mut().zip(times) {
+            segment.personal_best = time.map(Some);
+        }
+        true
+    }
+
+    /// How far ahead (negative) or behind (positive) of the personal best split `index` is
+    pub fn delta(&self, times: &[Timed<f32>], index: usize, method: TimingMethod) -> Option<f32> {
+        Some(times[index].get(method) - (*self.segments.get(index)?.personal_best.get(method))?)
+    }
+
+    pub fn draw<D: RaylibDraw>(
+        &self,
+        d: &mut D,
+        times: &[Timed<f32>],
+        golds: &[bool],
+        method: TimingMethod,
+        right: i32,
+    ) {
+        let mut y = 10;
+        for (index, segment) in self.segments.iter().enumerate() {
+            let (text, color) = if index < times.len() {
+                match self.delta(times, index, method) {
+                    Some(delta) => (
+                        format_delta(delta),
+                        if golds.get(index).copied().unwrap_or(false) {
+                            Color::GOLD
+                        } else if delta < 0.0 {
+                            Color::GREEN
+                        } else {
+                            Color::RED
+                        },
+                    ),
+                    None => (format_time(*times[index].get(method)), Color::WHITE),
+                }
+            } else {
+                (
+                    segment
+                        .personal_best
+                        .get(method)
+                        .map_or_else(|| "-".to_owned(), format_time),
+                    Color::LIGHTGRAY,
+                )
+            };
+            d.draw_text(&segment.name, right - 220, y, 20, Color::WHITE);
+            d.draw_text(&text, right - measure_text(&text, 20), y, 20, color);
+            y += 22;
+        }
+
+        let sum_of_best = format!(
+            "Sum of best: {}",
+            self.sum_of_best(method)
+                .map_or_else(|| "-".to_owned(), format_time)
+        );
+        d.draw_text(
+            &sum_of_best,
+            right - measure_text(&sum_of_best, 20),
+            y + 6,
+            20,
+            Color::LIGHTGRAY,
+        );
+    }
+
+    pub fn to_lss(&self) -> Result<String> {
+        let run = Run {
+            version: "1.7.0",
+            game_icon: (),
+            game_name: "Catch the Countdown!".to_owned(),
+            category_name: "Any%".to_owned(),
+            offset: "00:00:00".to_owned(),
+            attempt_count: self.attempts,
+            attempt_history: AttemptHistory {
+                attempts: self
+                    .history
+                    .iter()
+                    .map(|attempt| LssAttempt {
+                        id: attempt.id,
+                        real_time: attempt.time.real,
+                        game_time: attempt.time.game,
+                    })
+                    .collect(),
+            },
+            segments: LssSegments {
+                segments: self
+                    .segments
+                    .iter()
+                    .map(|segment| LssSegment {
+                        name: segment.name.clone(),
+                        icon: (),
+                        split_times: SplitTimes {
+                            split_times: vec![SplitTime {
+                                name: PERSONAL_BEST.to_owned(),
+                                real_time: segment.personal_best.real,
+                                game_time: segment.personal_best.game,
+                            }],
+                        },
+                        best_segment_time: LssTime {
+                            real_time: segment.best_segment.real,
+                            game_time: segment.best_segment.game,
+                        },
+                        segment_history: (),
+                    })
+                    .collect(),
+            },
+            auto_splitter_settings: (),
+        };
+
+        let mut lss = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let mut serializer = quick_xml::se::Serializer::with_root(&mut lss, Some("Run"))?;
+        serializer.indent(' ', 2);
+        run.serialize(serializer)?;
+        lss.push('\n');
+        Ok(lss)
+    }
+
+    pub fn from_lss(lss: &str) -> Result<Self> {
+        let run: Run = quick_xml::de::from_str(lss).context("Not a valid splits file!")?;
+        Ok(Self {
+            attempts: run.attempt_count,
+            history: run
+                .attempt_history
+                .attempts
+                .into_iter()
+                .map(|attempt| Attempt {
+                    id: attempt.id,
+                    time: Timed {
+                        real: attempt.real_time,
+                        game: attempt.game_time,
+                    },
+                })
+                .collect(),
+            segments: run
+                .segments
+                .segments
+                .into_iter()
+                .map(|segment| Segment {
+                    personal_best: segment
+                        .split_times
+                        .split_times
+                        .into_iter()
+                        .find(|split_time| split_time.name == PERSONAL_BEST)
+                        .map_or_else(Timed::default, |split_time| Timed {
+                            real: split_time.real_time,
+                            game: split_time.game_time,
+                        }),
+                    best_segment: Timed {
+                        real: segment.best_segment_time.real_time,
+                        game: segment.best_segment_time.game_time,
+                    },
+                    name: segment.name,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomic(path.as_ref(), self.to_lss()?.as_bytes())
+    }
+
+    /// Loads splits, or `None` if there is no splits file at `path` yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::from_lss(&std::fs::read_to_string(path)?)
+            .map(Some)
+            .with_context(|| format!("Failed to read splits '{}'!", path.display()))
+    }
+}
+
+pub fn format_delta(delta: f32) -> String {
+    format!("{}{:.2}", if delta < 0.0 { '-' } else { '+' }, delta.abs())
+}
+
+/// LiveSplit's `[d.]hh:mm:ss.fffffff`, rounded to its 100 ns ticks
+fn format_lss_time(time: f32) -> String {
+    let ticks = (time.max(0.0) as f64 * 1e7).round() as u64;
+    let seconds = ticks / 10_000_000;
+    let (days, hours) = (seconds / 86400, seconds / 3600 % 24);
+    let time = format!(
+        "{:02}:{:02}:{:02}.{:07}",
+        hours,
+        seconds / 60 % 60,
+        seconds % 60,
+        ticks % 10_000_000
+    );
+    if days > 0 {
+        format!("{}.{}", days, time)
+    } else {
+        time
+    }
+}
+
+fn parse_lss_time(time: &str) -> Result<f32> {
+    let time = time.trim();
+    let (days, time) = match time.split_once(':') {
+        Some((hours, _)) if hours.contains('.') => time.split_once('.').unwrap(),
+        _ => ("0", time),
+    };
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f32>()?;
+    }
+    Ok(days.parse::<f32>()? * 86400.0 + seconds)
+}
+
+const PERSONAL_BEST: &str = "Personal Best";
+
+// The parts of a LiveSplit `.lss` file the game reads and writes, everything else is skipped.
+// Icons and the segment history are only written, and empty.
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Run {
+    #[serde(rename = "@version", skip_deserializing)]
+    version: &'static str,
+    #[serde(skip_deserializing)]
+    game_icon: (),
+    #[serde(default)]
+    game_name: String,
+    #[serde(default)]
+    category_name: String,
+    #[serde(default)]
+    offset: String,
+    #[serde(default)]
+    attempt_count: u32,
+    #[serde(default)]
+    attempt_history: AttemptHistory,
+    segments: LssSegments,
+    #[serde(skip_deserializing)]
+    auto_splitter_settings: (),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AttemptHistory {
+    #[serde(rename = "Attempt", default)]
+    attempts: Vec<LssAttempt>,
+}
+
+// The times are repeated instead of flattening an `LssTime`, quick-xml can't read flattened fields
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LssAttempt {
+    #[serde(rename = "@id")]
+    id: u32,
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    real_time: Option<f32>,
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    game_time: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LssSegments {
+    #[serde(rename = "Segment", default)]
+    segments: Vec<LssSegment>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LssSegment {
+    #[serde(default)]
+    name: String,
+    #[serde(skip_deserializing)]
+    icon: (),
+    #[serde(default)]
+    split_times: SplitTimes,
+    #[serde(default)]
+    best_segment_time: LssTime,
+    #[serde(skip_deserializing)]
+    segment_history: (),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SplitTimes {
+    #[serde(rename = "SplitTime", default)]
+    split_times: Vec<SplitTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SplitTime {
+    #[serde(rename = "@name", default)]
+    name: String,
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    real_time: Option<f32>,
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    game_time: Option<f32>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LssTime {
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    real_time: Option<f32>,
+    #[serde(default, with = "lss_time", skip_serializing_if = "Option::is_none")]
+    game_time: Option<f32>,
+}
+
+/// Times in seconds, written the way LiveSplit does
+mod lss_time {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &Option<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&super::format_lss_time(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<f32>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|time| super::parse_lss_time(&time).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shaped like a file written by LiveSplit itself, with the parts this game never writes
+    const LIVESPLIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Run version="1.7.0">
+  <GameIcon><![CDATA[AAEAAAD/////AQAAAAAAAAAMAgAAAFFTeXN0ZW0uRHJhd2luZw==]]></GameIcon>
+  <GameName>Catch the Countdown!</GameName>
+  <CategoryName>Any%</CategoryName>
+  <LayoutPath>
+  </LayoutPath>
+  <Metadata>
+    <Run id="" />
+    <Platform usesEmulator="False">
+    </Platform>
+    <Region>
+    </Region>
+    <Variables />
+  </Metadata>
+  <Offset>00:00:00</Offset>
+  <AttemptCount>3</AttemptCount>
+  <AttemptHistory>
+    <Attempt id="1" started="10/17/2026 12:00:00" isStartedSynced="True" ended="10/17/2026 12:01:02" isEndedSynced="True">
+      <RealTime>00:01:02.5000000</RealTime>
+      <GameTime>00:00:58.2500000</GameTime>
+    </Attempt>
+    <Attempt id="2" started="10/17/2026 12:05:00" isStartedSynced="True" ended="10/17/2026 12:05:10" isEndedSynced="True" />
+    <Attempt started='10/18/2026 09:00:00' id='3'>
+      <RealTime>1.02:03:04.5000000</RealTime>
+    </Attempt>
+  </AttemptHistory>
+  <Segments>
+    <Segment>
+      <Name>Spikes &amp; &lt;Spiders&gt; &#39;n&#x27; more</Name>
+      <Icon />
+      <SplitTimes>
+        <SplitTime name="Personal Best">
+          <RealTime>00:00:05.5000000</RealTime>
+          <GameTime>00:00:05.2500000</GameTime>
+        </SplitTime>
+      </SplitTimes>
+      <BestSegmentTime>
+        <RealTime>00:00:05.1250000</RealTime>
+      </BestSegmentTime>
+      <SegmentHistory>
+        <Time id="1">
+          <RealTime>00:00:05.5000000</RealTime>
+          <GameTime>00:00:05.2500000</GameTime>
+        </Time>
+        <Time id="2" />
+      </SegmentHistory>
+    </Segment>
+    <Segment>
+      <Name><![CDATA[Level <2>]]></Name>
+      <Icon><![CDATA[AAEAAAD/////]]></Icon>
+      <SplitTimes>
+        <SplitTime name="Personal Best" />
+      </SplitTimes>
+      <BestSegmentTime />
+      <SegmentHistory />
+    </Segment>
+  </Segments>
+  <AutoSplitterSettings />
+</Run>
+"#;
+
+    #[test]
+    fn reads_livesplit_files() {
+        let splits = Splits::from_lss(LIVESPLIT).unwrap();
+        assert_eq!(splits.attempts, 3);
+
+        let ids: Vec<_> = splits.history.iter().map(|attempt| attempt.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(
+            splits.history[0].time,
+            Timed {
+                real: Some(62.5),
+                game: Some(58.25),
+            }
+        );
+        assert_eq!(splits.history[1].time, Timed::default());
+        assert_eq!(
+            splits.history[2].time,
+            Timed {
+                real: Some(86400.0 + 7384.5),
+                game: None,
+            }
+        );
+
+        assert_eq!(splits.segments.len(), 2);
+        let [first, second] = &splits.segments[..] else {
+            unreachable!();
+        };
+        assert_eq!(first.name, "Spikes & <Spiders> 'n' more");
+        assert_eq!(
+            first.personal_best,
+            Timed {
+                real: Some(5.5),
+                game: Some(5.25),
+            }
+        );
+        assert_eq!(
+            first.best_segment,
+            Timed {
+                real: Some(5.125),
+                game: None,
+            }
+        );
+        assert_eq!(second.name, "Level <2>");
+        assert_eq!(second.personal_best, Timed::default());
+        assert_eq!(second.best_segment, Timed::default());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut splits = Splits::new(3);
+        splits.segments[0].name = "Tom & \"Jerry's\" <level>".to_owned();
+        splits.segments[0].personal_best = Timed {
+            real: Some(5.5),
+            game: Some(5.25),
+        };
+        splits.segments[1].best_segment = Timed {
+            real: None,
+            game: Some(3725.125),
+        };
+        splits.start_attempt();
+        splits.start_attempt();
+        splits.history[0].time.real = Some(62.5);
+
+        let lss = splits.to_lss().unwrap();
+        assert_eq!(Splits::from_lss(&lss).unwrap(), splits);
+    }
+
+    #[test]
+    fn lss_times() {
+        assert_eq!(format_lss_time(62.5), "00:01:02.5000000");
+        assert_eq!(format_lss_time(3725.125), "01:02:05.1250000");
+        assert_eq!(format_lss_time(90061.5), "1.01:01:01.5000000");
+        for time in [0.0, 62.5, 3725.125, 90061.5] {
+            assert_eq!(parse_lss_time(&format_lss_time(time)).unwrap(), time);
+        }
+    }
+}
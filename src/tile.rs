@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::assets::*;
+
+/// IntGrid layer the level geometry is read from
+pub const LEVEL_LAYER: &str = "Level";
+
+/// Width of a spike's base, matching the sprite
+const SPIKE_BASE: f32 = 10.0;
+/// How far a spike sticks out from the tile it sits on
+const SPIKE_HEIGHT: f32 = 9.0;
+
+/// Part of a cell a tile takes up when touching it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Full,
+    /// Triangle pointing away from the tile it sits on, up or down, or sideways for `side` spikes
+    Spike {
+        side: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// How a kind of tile behaves, every cell of the level is one of these
+#[derive(Debug)]
+pub struct Tile {
+    /// Identifier of the IntGrid value in LDtk
+    pub identifier: &'static str,
+    /// The player can't move through it
+    pub solid: bool,
+    /// Touching it kills the player
+    pub damage: bool,
+    /// How quickly the player speeds up and slows down standing on it, 1 is normal
+    pub friction: f32,
+    /// Part of the speed the player keeps when bumping into it
+    pub bounciness: f32,
+    pub shape: Shape,
+}
+
+impl Tile {
+    /// Speed after bumping into the tile at `speed`
+    pub fn bounce(&self, speed: f32) -> f32 {
+        if self.bounciness > 0.0 {
+            -speed * self.bounciness
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Cells without an IntGrid value
+pub const EMPTY: Tile = Tile {
+    identifier: "",
+    solid: false,
+    damage: false,
+    friction: 1.0,
+    bounciness: 0.0,
+    shape: Shape::Full,
+};
+
+/// The level's edges and the door, which block the player like a wall
+pub const WALL: Tile = Tile {
+    identifier: "",
+    solid: true,
+    damage: false,
+    friction: 1.0,
+    bounciness: 0.0,
+    shape: Shape::Full,
+};
+
+/// Every kind of tile the game knows, to add one define it in LDtk with the same identifier
+pub static TILES: [Tile; 3] = [
+    Tile {
+        identifier: "Tile",
+        ..WALL
+    },
+    Tile {
+        identifier: "Spike",
+        damage: true,
+        shape: Shape::Spike { side: false },
+        ..EMPTY
+    },
+    Tile {
+        identifier: "Side_Spike",
+        damage: true,
+        shape: Shape::Spike { side: true },
+        ..EMPTY
+    },
+];
+
+/// A tile placed in a level
+#[derive(Debug, Clone, Copy)]
+pub struct Cell {
+    pub tile: &'static Tile,
+    /// Where a spike points
+    pub facing: Direction,
+}
+
+impl Cell {
+    /// `solid` tells whether the neighbouring tile in a direction is solid, spikes point away from it
+    pub fn new(tile: &'static Tile, solid: impl Fn(Direction) -> bool) -> Self {
+        let facing = match tile.shape {
+            Shape::Spike { side: false } if !solid(Direction::Down) && solid(Direction::Up) => {
+                Direction::Down
+            }
+            Shape::Spike { side: true } if !solid(Direction::Left) && solid(Direction::Right) => {
+                Direction::Left
+            }
+            Shape::Spike { side: true } => Direction::Right,
+            _ => Direction::Up,
+        };
+        Self { tile, facing }
+    }
+
+    /// Whether `rect` overlaps the tile, with the cell's top left corner at `position`
+    pub fn touches(&self, position: Vector2, rect: Rectangle) -> bool {
+        match self.tile.shape {
+            Shape::Full => rect.check_collision_recs(&rrect(position.x, position.y, 16, 16)),
+            Shape::Spike { .. } => {
+                let corners = [
+                    rvec2(rect.x, rect.y),
+                    rvec2(rect.x + rect.width, rect.y),
+                    rvec2(rect.x + rect.width, rect.y + rect.height),
+                    rvec2(rect.x, rect.y + rect.height),
+                ];
+                overlaps(&self.spike(position), &corners)
+            }
+        }
+    }
+
+    fn spike(&self, position: Vector2) -> [Vector2; 3] {
+        // Pointing up, then turned to face the right way
+        let points = [
+            rvec2(8.0 - SPIKE_BASE / 2.0, 16.0),
+            rvec2(8.0 + SPIKE_BASE / 2.0, 16.0),
+            rvec2(8.0, 16.0 - SPIKE_HEIGHT),
+        ];
+        points.map(|point| {
+            position
+                + match self.facing {
+                    Direction::Up => point,
+                    Direction::Down => rvec2(point.x, 16.0 - point.y),
+                    Direction::Left => rvec2(point.y, point.x),
+                    Direction::Right => rvec2(16.0 - point.y, point.x),
+                }
+        })
+    }
+}
+
+/// Separating axis test between two convex polygons
+fn overlaps(a: &[Vector2], b: &[Vector2]) -> bool {
+    for polygon in [a, b] {
+        for (i, start) in polygon.iter().enumerate() {
+            let end = polygon[(i + 1) % polygon.len()];
+            let axis = rvec2(start.y - end.y, end.x - start.x);
+            let project = |points: &[Vector2]| {
+                points
+                    .iter()
+                    .map(|point| point.x * axis.x + point.y * axis.y)
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                        (min.min(value), max.max(value))
+                    })
+            };
+            let ((a_min, a_max), (b_min, b_max)) = (project(a), project(b));
+            if a_max <= b_min || b_max <= a_min {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// What the IntGrid values of a project stand for
+#[derive(Clone)]
+pub struct TileSet {
+    values: HashMap<i64, &'static Tile>,
+}
+
+impl TileSet {
+    /// Reads the IntGrid values of the level layer from the project's definitions
+    pub fn parse(text: &str) -> Result<Self> {
+        let project: Value = serde_json::from_str(text).context("Levels are not valid JSON!")?;
+        let layer = project["defs"]["layers"]
+            .as_array()
+            .and_then(|layers| {
+                layers
+                    .iter()
+                    .find(|layer| layer["identifier"] == LEVEL_LAYER)
+            })
+            .context("No level layer definition found!")?;
+
+        let mut values = HashMap::from([(0, &EMPTY)]);
+        for value in layer["intGridValues"].as_array().into_iter().flatten() {
+            let number = value["value"]
+                .as_i64()
+                .context("IntGrid value has no number!")?;
+            let identifier = value["identifier"]
+                .as_str()
+                .with_context(|| format!("IntGrid value {} has no identifier!", number))?;
+            let tile = TILES
+                .iter()
+                .find(|tile| tile.identifier == identifier)
+                .with_context(|| format!("Unknown tile '{}'!", identifier))?;
+            values.insert(number, tile);
+        }
+        Ok(Self { values })
+    }
+
+    pub fn get(&self, value: i64) -> Option<&'static Tile> {
+        self.values.get(&value).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn project(values: Value) -> String {
+        json!({ "defs": { "layers": [{ "identifier": LEVEL_LAYER, "intGridValues": values }] } })
+            .to_string()
+    }
+
+    fn identifier(tiles: &TileSet, value: i64) -> Option<&'static str> {
+        tiles.get(value).map(|tile| tile.identifier)
+    }
+
+    #[test]
+    fn parses_int_grid_values() {
+        let tiles = TileSet::parse(&project(json!([
+            { "value": 1, "identifier": "Tile" },
+            { "value": 3, "identifier": "Side_Spike" },
+        ])))
+        .unwrap();
+        assert_eq!(identifier(&tiles, 0), Some(EMPTY.identifier));
+        assert_eq!(identifier(&tiles, 1), Some("Tile"));
+        assert_eq!(identifier(&tiles, 2), None);
+        assert_eq!(identifier(&tiles, 3), Some("Side_Spike"));
+
+        assert!(TileSet::parse("{").is_err());
+        assert!(TileSet::parse(&json!({ "defs": { "layers": [] } }).to_string()).is_err());
+        assert!(TileSet::parse(&project(json!([{ "identifier": "Tile" }]))).is_err());
+        assert!(TileSet::parse(&project(json!([{ "value": 1 }]))).is_err());
+    }
+
+    #[test]
+    fn unknown_tiles_are_rejected() {
+        let error = TileSet::parse(&project(json!([{ "value": 1, "identifier": "Lava" }])))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Unknown tile 'Lava'"));
+    }
+
+    #[test]
+    fn registered_tiles() {
+        let tile = |identifier| {
+            TILES
+                .iter()
+                .find(|tile| tile.identifier == identifier)
+                .unwrap()
+        };
+        let flags = |tile: &Tile| (tile.solid, tile.damage, tile.shape);
+        assert_eq!(flags(tile("Tile")), (true, false, Shape::Full));
+        assert_eq!(
+            flags(tile("Spike")),
+            (false, true, Shape::Spike { side: false })
+        );
+        assert_eq!(
+            flags(tile("Side_Spike")),
+            (false, true, Shape::Spike { side: true })
+        );
+        // None of them bounce the player back
+        for tile in &TILES {
+            assert_eq!(tile.friction, 1.0, "{}", tile.identifier);
+            assert_eq!(tile.bounce(100.0), 0.0, "{}", tile.identifier);
+        }
+        assert_eq!(
+            Tile {
+                bounciness: 0.5,
+                ..WALL
+            }
+            .bounce(100.0),
+            -50.0
+        );
+    }
+
+    #[test]
+    fn spikes_point_away_from_what_they_sit_on() {
+        let facing = |tile: &'static Tile, solid: &[Direction]| {
+            Cell::new(tile, |direction| solid.contains(&direction)).facing
+        };
+        let (tile, spike, side) = (&TILES[0], &TILES[1], &TILES[2]);
+        assert_eq!(facing(spike, &[Direction::Down]), Direction::Up);
+        assert_eq!(facing(spike, &[Direction::Up]), Direction::Down);
+        assert_eq!(
+            facing(spike, &[Direction::Up, Direction::Down]),
+            Direction::Up
+        );
+        assert_eq!(facing(spike, &[]), Direction::Up);
+        assert_eq!(facing(side, &[Direction::Left]), Direction::Right);
+        assert_eq!(facing(side, &[Direction::Right]), Direction::Left);
+        assert_eq!(
+            facing(side, &[Direction::Left, Direction::Right]),
+            Direction::Right
+        );
+        assert_eq!(facing(side, &[]), Direction::Right);
+        assert_eq!(facing(tile, &[Direction::Up]), Direction::Up);
+    }
+
+    /// `rect` given for a spike pointing up, turned along with the spike to `facing`
+    fn turned(facing: Direction, rect: Rectangle) -> Rectangle {
+        let Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = rect;
+        match facing {
+            Direction::Up => rect,
+            Direction::Down => rrect(x, 16.0 - y - height, width, height),
+            Direction::Left => rrect(y, x, height, width),
+            Direction::Right => rrect(16.0 - y - height, x, height, width),
+        }
+    }
+
+    #[test]
+    fn spikes_only_hurt_inside_the_triangle() {
+        let position = rvec2(32, 48);
+        // Pointing up the triangle is (3, 16), (13, 16) and (8, 7) within its cell
+        let cases = [
+            // Within the cell and the triangle's bounds, but just outside its slanted sides
+            (rrect(4.0, 11.0, 1.0, 1.0), false),
+            (rrect(11.0, 11.0, 1.0, 1.0), false),
+            (rrect(5.0, 11.0, 1.0, 1.0), true),
+            (rrect(10.0, 11.0, 1.0, 1.0), true),
+            // Above the tip, and touching it
+            (rrect(7.5, 5.0, 1.0, 1.9), false),
+            (rrect(7.5, 5.0, 1.0, 2.1), true),
+            // Next to the base, and over it
+            (rrect(0.0, 14.0, 2.9, 2.0), false),
+            (rrect(0.0, 14.0, 3.1, 2.0), true),
+            // The rest of the cell is empty
+            (rrect(0.0, 0.0, 16.0, 6.0), false),
+            (rrect(0.0, 0.0, 16.0, 16.0), true),
+        ];
+        for facing in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            let cell = Cell {
+                tile: &TILES[1],
+                facing,
+            };
+            for (rect, hit) in cases {
+                let rect = turned(facing, rect);
+                let rect = rrect(
+                    rect.x + position.x,
+                    rect.y + position.y,
+                    rect.width,
+                    rect.height,
+                );
+                assert_eq!(cell.touches(position, rect), hit, "{:?} {:?}", facing, rect);
+            }
+        }
+    }
+
+    #[test]
+    fn full_tiles_cover_their_cell() {
+        let cell = Cell::new(&TILES[0], |_| false);
+        let position = rvec2(16, 16);
+        assert!(cell.touches(position, rrect(31, 31, 2, 2)));
+        assert!(!cell.touches(position, rrect(32.5, 16, 4, 16)));
+    }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// Which clock runs are compared by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TimingMethod {
+    /// Everything from the start of the run, including pauses and level transitions
+    #[default]
+    RealTime,
+    /// Only the time spent actually playing levels
+    GameTime,
+}
+
+impl TimingMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            TimingMethod::RealTime => "RTA",
+            TimingMethod::GameTime => "IGT",
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            TimingMethod::RealTime => TimingMethod::GameTime,
+            TimingMethod::GameTime => TimingMethod::RealTime,
+        }
+    }
+}
+
+/// A value measured with both clocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timed<T> {
+    pub real: T,
+    pub game: T,
+}
+
+impl<T> Timed<T> {
+    pub fn get(&self, method: TimingMethod) -> &T {
+        match method {
+            TimingMethod::RealTime => &self.real,
+            TimingMethod::GameTime => &self.game,
+        }
+    }
+
+    pub fn get_mut(&mut self, method: TimingMethod) -> &mut T {
+        match method {
+            TimingMethod::RealTime => &mut self.real,
+            TimingMethod::GameTime => &mut self.game,
+        }
+    }
+
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Timed<U> {
+        Timed {
+            real: f(self.real),
+            game: f(self.game),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::assets::*;
+use crate::input::{Action, Input};
+
+/// What is drawn on a button
+#[derive(Clone, Copy)]
+pub enum Label<'a> {
+    Texture(&'a Texture2D),
+    /// Text at the same pixel scale as the button textures
+    Text(&'a str),
+}
+
+impl Label<'_> {
+    fn size(&self) -> Vector2 {
+        match self {
+            Label::Texture(texture) => rvec2(texture.width, texture.height),
+            Label::Text(text) => rvec2(measure_text(text, 10), 10),
+        }
+    }
+}
+
+pub struct Button {
+    pub scale: f32,
+    last_hovered: bool,
+    position: Vector2,
+}
+
+impl Button {
+    pub fn new(position: Vector2) -> Self {
+        Self {
+            scale: 0.0,
+            last_hovered: false,
+            position,
+        }
+    }
+
+    /// Returns whether the button was clicked. `selected` highlights it like a mouse hover,
+    /// for keyboard navigation.
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        label: Label,
+        selected: bool,
+        audio: &mut RaylibAudio,
+        hover_sound: &Sound,
+        click_sound: &Sound,
+    ) -> bool {
+        let mouse_over = self
+            .rect(label)
+            .check_collision_point_rec(rl.get_mouse_position());
+        let hovered = mouse_over || selected;
+
+        if hovered && !self.last_hovered {
+            audio.play_sound(hover_sound);
+        }
+
+        self.last_hovered = hovered;
+        self.scale += (if hovered { 1.3 } else { 1.0 } * 3.0 - self.scale)
+            * (1.0 - 0.5_f32.powf(rl.get_frame_time() / 0.3));
+
+        if mouse_over && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            audio.play_sound(click_sound);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D, label: Label, tint: Color) {
+        let tl = self.tl(label);
+        match label {
+            Label::Texture(texture) => d.draw_texture_ex(texture, tl, 0.0, self.scale, tint),
+            Label::Text(text) => {
+                d.draw_text(text, tl.x as _, tl.y as _, (10.0 * self.scale) as i32, tint)
+            }
+        }
+    }
+
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    pub fn tl(&self, label: Label) -> Vector2 {
+        self.position - label.size() / 2.0 * self.scale
+    }
+
+    pub fn rect(&self, label: Label) -> Rectangle {
+        let tl = self.tl(label);
+        let size = label.size() * self.scale;
+        rrect(tl.x, tl.y, size.x, size.y)
+    }
+}
+
+/// A column of text buttons, navigable with the mouse or the keyboard
+pub struct Menu {
+    selected: usize,
+    buttons: Vec<Button>,
+}
+
+impl Menu {
+    pub fn new(center: Vector2, count: usize) -> Self {
+        let top = center.y - (count - 1) as f32 * 25.0;
+        Self {
+            selected: 0,
+            buttons: (0..count)
+                .map(|index| Button::new(rvec2(center.x, top + index as f32 * 50.0)))
+                .collect(),
+        }
+    }
+
+    /// Returns the index of the entry that was activated
+    pub fn update(
+        &mut self,
+        rl: &mut RaylibHandle,
+        assets: &mut Assets,
+        input: &Input,
+        labels: &[&str],
+    ) -> Option<usize> {
+        let count = self.buttons.len();
+        if input.pressed(Action::Down) {
+            self.selected = (self.selected + 1) % count;
+        }
+        if input.pressed(Action::Up) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+
+        let mut choice = None;
+        for (index, button) in self.buttons.iter_mut().enumerate() {
+            if button.update(
+                rl,
+                Label::Text(labels[index]),
+                self.selected == index,
+                &mut assets.audio,
+                &assets.button_hover_sound,
+                &assets.button_click_sound,
+            ) {
+                choice = Some(index);
+            }
+        }
+        if input.pressed(Action::Confirm) {
+            assets.audio.play_sound(&assets.button_click_sound);
+            choice = Some(self.selected);
+        }
+        choice
+    }
+
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D, labels: &[&str]) {
+        for (button, label) in self.buttons.iter().zip(labels) {
+            button.draw(d, Label::Text(label), Color::WHITE);
+        }
+    }
+}
+
+/// Darkens whatever was drawn before, for menus on top of other screens
+pub fn dim<D: RaylibDraw>(d: &mut D, screen: Vector2) {
+    d.draw_rectangle_v(Vector2::zero(), screen, Color::new(0, 0, 0, 160));
+}
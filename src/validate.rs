@@ -0,0 +1,782 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::assets::*;
+use crate::expression;
+use crate::level::{Number, TRACKS};
+use crate::physics::{override_problem, OVERRIDES};
+use crate::sequence::{SequenceRule, SEQUENCE_FIELD};
+use crate::tile::{Tile, TileSet, EMPTY, LEVEL_LAYER};
+
+/// A problem in the levels that would show up (or crash) at runtime
+pub struct Issue {
+    pub level: String,
+    /// Entity instance the issue is about
+    pub entity: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.entity {
+            Some(iid) => write!(f, "{} [{}]: {}", self.level, iid, self.message),
+            None => write!(f, "{}: {}", self.level, self.message),
+        }
+    }
+}
+
+/// Checks a whole LDtk project, without loading it into the game
+pub fn validate(text: &str) -> Result<Vec<Issue>> {
+    let project: Value = serde_json::from_str(text).context("Levels are not valid JSON!")?;
+    let tiles = TileSet::parse(text)?;
+    let levels = project["levels"]
+        .as_array()
+        .context("Project has no levels!")?;
+
+    let mut issues = Vec::new();
+    for level in levels {
+        LevelCheck::new(level, &tiles, &mut issues).run();
+    }
+    Ok(issues)
+}
+
+struct LevelCheck<'a> {
+    level: &'a Value,
+    tiles: &'a TileSet,
+    name: String,
+    issues: &'a mut Vec<Issue>,
+}
+
+impl<'a> LevelCheck<'a> {
+    fn new(level: &'a Value, tiles: &'a TileSet, issues: &'a mut Vec<Issue>) -> Self {
+        Self {
+            level,
+            tiles,
+            name: level["identifier"]
+                .as_str()
+                .unwrap_or("<unnamed>")
+                .to_owned(),
+            issues,
+        }
+    }
+
+    fn report(&mut self, entity: Option<&Value>, message: impl Into<String>) {
+        self.issues.push(Issue {
+            level: self.name.clone(),
+            entity: entity.map(|entity| entity["iid"].as_str().unwrap_or("?").to_owned()),
+            message: message.into(),
+        });
+    }
+
+    fn layer(&mut self, identifier: &str, kind: &str) -> Option<&'a Value> {
+        let layer = self.level["layerInstances"].as_array().and_then(|layers| {
+            layers
+                .iter()
+                .find(|layer| layer["__identifier"] == identifier)
+        });
+        match layer {
+            None => self.report(None, format!("Missing layer '{}'", identifier)),
+            Some(layer) if layer["__type"] != kind => self.report(
+                None,
+                format!(
+                    "Layer '{}' is {}, expected {}",
+                    identifier, layer["__type"], kind
+                ),
+            ),
+            Some(layer) => return Some(layer),
+        }
+        None
+    }
+
+    fn run(mut self) {
+        let target = field(&self.level["fieldInstances"], "TargetNumber");
+        let target = match target.map(|target| &target["__value"]) {
+            None => {
+                self.report(None, "Missing field 'TargetNumber'");
+                None
+            }
+            Some(value) => {
+                let target = value.as_i64().and_then(|value| i32::try_from(value).ok());
+                if target.is_none() {
+                    self.report(None, "Field 'TargetNumber' is not an Int");
+                }
+                target
+            }
+        };
+
+        let rule = self.rule(None, Some(SequenceRule::CountDown(1)));
+
+        for name in OVERRIDES {
+            let value = field(&self.level["fieldInstances"], name).map(|field| &field["__value"]);
+            if value.is_some_and(|value| !value.is_null() && !value.is_number()) {
+                self.report(None, format!("Field '{}' is not a number", name));
+            } else if let Some(problem) = value
+                .and_then(Value::as_f64)
+                .and_then(|value| override_problem(name, value))
+            {
+                self.report(None, problem);
+            }
+        }
+
+        let grid = self
+            .layer(LEVEL_LAYER, "IntGrid")
+            .map(|layer| self.grid(layer));
+        let Some(layer) = self.layer("Entities", "Entities") else {
+            return;
+        };
+        // Points are in cells of the entity layer, which are usually single pixels
+        let point_size = layer["__gridSize"].as_i64().unwrap_or(1);
+        let entities = layer["entityInstances"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+
+        let mut numbers = Vec::new();
+        let mut players = Vec::new();
+        let mut doors = Vec::new();
+        for entity in entities {
+            match entity["__identifier"].as_str().unwrap_or_default() {
+                "Player" => players.push(entity),
+                "Door" => doors.push(entity),
+                "Number" => numbers.extend(self.track(entity).zip(self.number(entity))),
+                "Expression" => numbers.extend(self.track(entity).zip(self.expression(entity))),
+                "Spider" => {
+                    numbers.extend(self.track(entity).zip(self.number(entity)));
+                    self.spider(entity, point_size, grid.as_ref());
+                }
+                "Web" => (),
+                other => self.report(Some(entity), format!("Unknown entity '{}'", other)),
+            }
+        }
+
+        if players.len() != 1 {
+            self.report(
+                None,
+                format!("Expected one Player, found {}", players.len()),
+            );
+        }
+        if doors.is_empty() {
+            self.report(None, "Expected a Door, found none");
+        }
+        let mut tracks = Vec::new();
+        for door in &doors {
+            let Some(track) = self.track(door) else {
+                continue;
+            };
+            if tracks.contains(&track) {
+                self.report(
+                    Some(door),
+                    format!("More than one Door for the {} countdown", track),
+                );
+                continue;
+            }
+            tracks.push(track);
+            // Doors count down like the level unless they have their own countdown
+            let target = match field(&door["fieldInstances"], "TargetNumber")
+                .map(|field| &field["__value"])
+            {
+                None | Some(Value::Null) => target,
+                Some(value) => {
+                    let target = value.as_i64().and_then(|value| i32::try_from(value).ok());
+                    if target.is_none() {
+                        self.report(Some(door), "Field 'TargetNumber' is not an Int");
+                    }
+                    target
+                }
+            };
+            if let (Some(target), Some(rule)) = (target, self.rule(Some(door), rule.clone())) {
+                let placed: Vec<_> = numbers
+                    .iter()
+                    .filter(|(other, _)| *other == track)
+                    .map(|(_, number)| *number)
+                    .collect();
+                let order = match rule.order(target) {
+                    Result::Ok(order) => order,
+                    Err(err) => {
+                        self.report(Some(door), err.to_string());
+                        continue;
+                    }
+                };
+                if order.is_empty() {
+                    self.report(
+                        Some(door),
+                        format!(
+                            "The {} countdown has no numbers, its door is open from the start",
+                            track
+                        ),
+                    );
+                }
+                self.sequence(track, &order, &placed);
+            }
+        }
+        for (track, _) in TRACKS {
+            if numbers.iter().any(|(other, _)| *other == track) && !tracks.contains(&track) {
+                self.report(None, format!("No Door for the {} countdown", track));
+            }
+        }
+        if let (Some(grid), Some(player)) = (&grid, players.first()) {
+            for door in doors {
+                if !grid.reaches_right_edge(door, player) {
+                    self.report(Some(door), "Door is not reachable from the right edge");
+                }
+            }
+        }
+    }
+
+    fn grid(&mut self, layer: &Value) -> Grid {
+        let width = layer["__cWid"].as_u64().unwrap_or_default() as usize;
+        let height = layer["__cHei"].as_u64().unwrap_or_default() as usize;
+        let size = layer["__gridSize"].as_u64().unwrap_or(16) as usize;
+        let values: Vec<i64> = layer["intGridCsv"]
+            .as_array()
+            .map(|cells| {
+                cells
+                    .iter()
+                    .map(|cell| cell.as_i64().unwrap_or(-1))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if values.len() != width * height {
+            self.report(
+                None,
+                format!(
+                    "Layer '{}' has {} cells, expected {}x{}",
+                    LEVEL_LAYER,
+                    values.len(),
+                    width,
+                    height
+                ),
+            );
+        }
+        let mut cells = Vec::new();
+        for (index, value) in values.iter().enumerate() {
+            let tile = self.tiles.get(*value);
+            if tile.is_none() {
+                self.report(
+                    None,
+                    format!(
+                        "Undefined tile {} at ({}, {})",
+                        value,
+                        index % width.max(1),
+                        index / width.max(1)
+                    ),
+                );
+            }
+            cells.push(tile.unwrap_or(&EMPTY));
+        }
+        Grid {
+            width,
+            height,
+            size,
+            cells,
+        }
+    }
+
+    /// The rule in the Sequence field of `entity`, or of the level without one
+    fn rule(
+        &mut self,
+        entity: Option<&Value>,
+        default: Option<SequenceRule>,
+    ) -> Option<SequenceRule> {
+        let fields = &entity.unwrap_or(self.level)["fieldInstances"];
+        match field(fields, SEQUENCE_FIELD).map(|field| &field["__value"]) {
+            None | Some(Value::Null) => default,
+            Some(Value::String(text)) => SequenceRule::parse(text)
+                .map_err(|err| self.report(entity, format!("Field '{}': {}", SEQUENCE_FIELD, err)))
+                .ok(),
+            Some(_) => {
+                self.report(
+                    entity,
+                    format!("Field '{}' is not a String", SEQUENCE_FIELD),
+                );
+                None
+            }
+        }
+    }
+
+    /// The countdown a number or door belongs to, White without a Track field
+    fn track(&mut self, entity: &Value) -> Option<&'static str> {
+        let value = field(&entity["fieldInstances"], "Track").map(|field| &field["__value"]);
+        let Some(value) = value.filter(|value| !value.is_null()) else {
+            return Some(TRACKS[0].0);
+        };
+        let track = TRACKS
+            .iter()
+            .map(|(name, _)| *name)
+            .find(|name| value.as_str() == Some(name));
+        if track.is_none() {
+            self.report(
+                Some(entity),
+                format!("Field 'Track' has unexpected value {}", value),
+            );
+        }
+        track
+    }
+
+    /// The number of a Number or Spider entity, if it is valid
+    fn number(&mut self, entity: &Value) -> Option<i32> {
+        let Some(value) = field(&entity["fieldInstances"], "Number") else {
+            self.report(Some(entity), "Missing field 'Number'");
+            return None;
+        };
+        let number = match &value["__value"] {
+            Value::String(text) => Number::parse(text).ok(),
+            value => value.as_i64().and_then(|value| i32::try_from(value).ok()),
+        };
+        if number.is_none() {
+            self.report(
+                Some(entity),
+                format!("Field 'Number' has unexpected value {}", value["__value"]),
+            );
+        }
+        number
+    }
+
+    /// The value of an Expression entity, if it can be worked out
+    fn expression(&mut self, entity: &Value) -> Option<i32> {
+        let Some(value) = field(&entity["fieldInstances"], "Expression") else {
+            self.report(Some(entity), "Missing field 'Expression'");
+            return None;
+        };
+        let Some(text) = value["__value"].as_str() else {
+            self.report(Some(entity), "Field 'Expression' is not a String");
+            return None;
+        };
+        expression::parse(text)
+            .and_then(|tokens| expression::evaluate(&tokens))
+            .map_err(|err| {
+                self.report(
+                    Some(entity),
+                    format!("Expression '{}' can't be worked out: {}", text, err),
+                )
+            })
+            .ok()
+    }
+
+    fn spider(&mut self, entity: &Value, point_size: i64, grid: Option<&Grid>) {
+        let Some(target) = field(&entity["fieldInstances"], "Target") else {
+            self.report(Some(entity), "Missing field 'Target'");
+            return;
+        };
+        let target = &target["__value"];
+        let (Some(x), Some(y)) = (target["cx"].as_i64(), target["cy"].as_i64()) else {
+            self.report(Some(entity), "Field 'Target' is not a Point");
+            return;
+        };
+        let Some(grid) = grid else {
+            return;
+        };
+        let size = grid.size.max(1) as i64;
+        match grid.cell(x * point_size / size, y * point_size / size) {
+            None => self.report(
+                Some(entity),
+                format!("Target ({}, {}) is outside the level", x, y),
+            ),
+            Some(tile) if tile.solid => self.report(
+                Some(entity),
+                format!("Target ({}, {}) is inside a solid tile", x, y),
+            ),
+            Some(_) => (),
+        }
+    }
+
+    /// Every number of the level's sequence has to be placed once for each time it comes up
+    fn sequence(&mut self, track: &str, order: &[i32], numbers: &[i32]) {
+        let name = match track {
+            "White" => "Number".to_owned(),
+            track => format!("{} number", track),
+        };
+        let count =
+            |list: &[i32], number: i32| list.iter().filter(|other| **other == number).count();
+        for (index, &number) in order.iter().enumerate() {
+            // Repeated numbers are only checked the first time
+            if order[..index].contains(&number) {
+                continue;
+            }
+            let (needed, placed) = (count(order, number), count(numbers, number));
+            if placed == 0 {
+                self.report(
+                    None,
+                    format!("{} {} is missing from the sequence", name, number),
+                );
+            } else if placed != needed {
+                self.report(
+                    None,
+                    format!(
+                        "{} {} is placed {} times, expected {}",
+                        name, number, placed, needed
+                    ),
+                );
+            }
+        }
+        for number in numbers {
+            if !order.contains(number) {
+                self.report(
+                    None,
+                    format!(
+                        "{} {} is not part of the sequence {:?}",
+                        name, number, order
+                    ),
+                );
+            }
+        }
+    }
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    size: usize,
+    cells: Vec<&'static Tile>,
+}
+
+impl Grid {
+    fn cell(&self, x: i64, y: i64) -> Option<&'static Tile> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        self.cells
+            .get(x as usize + y as usize * self.width)
+            .copied()
+    }
+
+    fn passable(&self, x: i64, y: i64) -> bool {
+        self.cell(x, y)
+            .is_some_and(|tile| !tile.solid && !tile.damage)
+    }
+
+    /// Flood fills from the door towards the last column, through gaps the player's hitbox fits
+    /// through. Cells are visited by the top left corner of the hitbox.
+    fn reaches_right_edge(&self, door: &Value, player: &Value) -> bool {
+        let size = self.size.max(1) as i64;
+        // Cells the door covers, it doesn't have to line up with the grid
+        let span = |axis: usize, key: &str| {
+            let start = door["px"][axis].as_i64().unwrap_or_default();
+            let end = start + door[key].as_i64().unwrap_or_default().max(1);
+            start / size..(end + size - 1) / size
+        };
+        let (columns, rows) = (span(0, "width"), span(1, "height"));
+        // The hitbox is a pixel smaller than the sprite, see `Player::hitbox`
+        let hitbox = |key: &str| ((player[key].as_i64().unwrap_or(1) - 1).max(1) + size - 1) / size;
+        let (player_width, player_height) = (hitbox("width"), hitbox("height"));
+
+        // The door itself opens, so its cells count as empty
+        let door_cell = |x, y| columns.contains(&x) && rows.contains(&y);
+        let free = |x, y| self.passable(x, y) || (door_cell(x, y) && self.cell(x, y).is_some());
+        let fits = |x: i64, y: i64| {
+            (x..x + player_width).all(|x| (y..y + player_height).all(|y| free(x, y)))
+        };
+
+        let mut visited = vec![false; self.cells.len()];
+        let mut queue = VecDeque::new();
+        for y in rows.start - player_height + 1..rows.end {
+            for x in columns.start - player_width + 1..columns.end {
+                if fits(x, y) {
+                    visited[x as usize + y as usize * self.width] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            if x + player_width >= self.width as i64 {
+                return true;
+            }
+            for (x, y) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if fits(x, y) && !visited[x as usize + y as usize * self.width] {
+                    visited[x as usize + y as usize * self.width] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+        false
+    }
+}
+
+fn field<'a>(fields: &'a Value, identifier: &str) -> Option<&'a Value> {
+    fields
+        .as_array()?
+        .iter()
+        .find(|field| field["__identifier"] == identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tile::WALL;
+
+    const LEVELS: &str = include_str!("../levels.ldtk");
+
+    /// Messages of the issues found after `edit` changed level `index`
+    fn check(index: usize, edit: impl FnOnce(&mut Value)) -> Vec<String> {
+        let mut project: Value = serde_json::from_str(LEVELS).unwrap();
+        edit(&mut project["levels"][index]);
+        validate(&project.to_string())
+            .unwrap()
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    fn reported(issues: &[String], message: &str) -> bool {
+        issues.iter().any(|issue| issue.contains(message))
+    }
+
+    /// Sets field `name`, adding it if the instance doesn't have it yet
+    fn set_field(fields: &mut Value, name: &str, value: Value) {
+        let fields = fields.as_array_mut().unwrap();
+        match fields
+            .iter_mut()
+            .find(|field| field["__identifier"] == name)
+        {
+            Some(field) => field["__value"] = value,
+            None => fields.push(json!({ "__identifier": name, "__value": value })),
+        }
+    }
+
+    fn entities(level: &mut Value) -> &mut Vec<Value> {
+        level["layerInstances"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|layer| layer["__identifier"] == "Entities")
+            .unwrap()["entityInstances"]
+            .as_array_mut()
+            .unwrap()
+    }
+
+    /// The first entity with `identifier`, and with `number` in its Number field if given
+    fn entity<'a>(level: &'a mut Value, identifier: &str, number: Option<&str>) -> &'a mut Value {
+        entities(level)
+            .iter_mut()
+            .find(|entity| {
+                entity["__identifier"] == identifier
+                    && number.is_none_or(|number| {
+                        field(&entity["fieldInstances"], "Number").unwrap()["__value"] == number
+                    })
+            })
+            .unwrap()
+    }
+
+    fn remove(level: &mut Value, identifier: &str) {
+        entities(level).retain(|entity| entity["__identifier"] != identifier);
+    }
+
+    #[test]
+    fn levels_are_valid() {
+        let issues = validate(LEVELS).unwrap();
+        assert!(issues.is_empty(), "{}", issues[0]);
+    }
+
+    #[test]
+    fn missing_layers() {
+        for layer in [LEVEL_LAYER, "Entities"] {
+            let issues = check(0, |level| {
+                let layers = level["layerInstances"].as_array_mut().unwrap();
+                layers.retain(|other| other["__identifier"] != layer);
+            });
+            assert!(reported(&issues, &format!("Missing layer '{}'", layer)));
+        }
+        let issues = check(0, |level| {
+            let layers = level["layerInstances"].as_array_mut().unwrap();
+            let layer = layers
+                .iter_mut()
+                .find(|layer| layer["__identifier"] == LEVEL_LAYER)
+                .unwrap();
+            layer["__type"] = "Entities".into();
+        });
+        assert!(reported(&issues, "expected IntGrid"));
+    }
+
+    #[test]
+    fn wrong_field_types() {
+        for (name, value, message) in [
+            (
+                "TargetNumber",
+                json!("3"),
+                "Field 'TargetNumber' is not an Int",
+            ),
+            (SEQUENCE_FIELD, json!(3), "Field 'Sequence' is not a String"),
+            (SEQUENCE_FIELD, json!("Sideways"), "Field 'Sequence'"),
+            ("Gravity", json!("fast"), "Field 'Gravity' is not a number"),
+        ] {
+            let issues = check(0, |level| {
+                set_field(&mut level["fieldInstances"], name, value)
+            });
+            assert!(reported(&issues, message), "{}: {:?}", name, issues);
+        }
+
+        let issues = check(0, |level| {
+            let number = entity(level, "Number", Some("Number1"));
+            set_field(&mut number["fieldInstances"], "Number", json!("One"));
+        });
+        assert!(reported(&issues, "Field 'Number' has unexpected value"));
+        let issues = check(0, |level| {
+            let number = entity(level, "Number", Some("Number1"));
+            set_field(&mut number["fieldInstances"], "Track", json!("Purple"));
+        });
+        assert!(reported(&issues, "Field 'Track' has unexpected value"));
+    }
+
+    #[test]
+    fn impossible_physics() {
+        for (name, value, message) in [
+            ("HalfLife", json!(0), "Field 'HalfLife' has to be positive"),
+            (
+                "JumpSpeed",
+                json!(-300),
+                "Field 'JumpSpeed' has to be positive",
+            ),
+            (
+                "MaxJumps",
+                json!(2.5),
+                "Field 'MaxJumps' has to be a whole number",
+            ),
+        ] {
+            let issues = check(0, |level| {
+                set_field(&mut level["fieldInstances"], name, value)
+            });
+            assert!(reported(&issues, message), "{}: {:?}", name, issues);
+        }
+        let issues = check(0, |level| {
+            set_field(&mut level["fieldInstances"], "MaxJumps", json!(3))
+        });
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn missing_player_or_door() {
+        let issues = check(0, |level| remove(level, "Player"));
+        assert!(reported(&issues, "Expected one Player, found 0"));
+        let issues = check(0, |level| {
+            let player = entity(level, "Player", None).clone();
+            entities(level).push(player);
+        });
+        assert!(reported(&issues, "Expected one Player, found 2"));
+        let issues = check(0, |level| remove(level, "Door"));
+        assert!(reported(&issues, "Expected a Door, found none"));
+    }
+
+    #[test]
+    fn duplicate_and_missing_numbers() {
+        let issues = check(0, |level| {
+            let number = entity(level, "Number", Some("Number2")).clone();
+            entities(level).push(number);
+        });
+        assert!(reported(&issues, "Number 2 is placed 2 times, expected 1"));
+
+        let issues = check(0, |level| {
+            entities(level).retain(|entity| {
+                field(&entity["fieldInstances"], "Number")
+                    .is_none_or(|number| number["__value"] != "Number3")
+            });
+        });
+        assert!(reported(&issues, "Number 3 is missing from the sequence"));
+
+        let issues = check(0, |level| {
+            let number = entity(level, "Number", Some("Number1"));
+            set_field(&mut number["fieldInstances"], "Number", json!("Number9"));
+        });
+        assert!(reported(&issues, "Number 9 is not part of the sequence"));
+    }
+
+    #[test]
+    fn empty_sequences_are_reported() {
+        let issues = check(0, |level| {
+            set_field(
+                &mut level["fieldInstances"],
+                SEQUENCE_FIELD,
+                json!("CountUp 10"),
+            );
+        });
+        assert!(reported(&issues, "has no numbers"));
+    }
+
+    #[test]
+    fn long_sequences_are_reported() {
+        let issues = check(0, |level| {
+            set_field(
+                &mut level["fieldInstances"],
+                "TargetNumber",
+                json!(i32::MAX),
+            );
+        });
+        assert!(
+            reported(&issues, "has more than 256 numbers"),
+            "{:?}",
+            issues
+        );
+    }
+
+    #[test]
+    fn spider_targets() {
+        let target = |cx: i64, cy: i64| {
+            check(7, move |level| {
+                let spider = entity(level, "Spider", None);
+                set_field(
+                    &mut spider["fieldInstances"],
+                    "Target",
+                    json!({ "cx": cx, "cy": cy }),
+                );
+            })
+        };
+        assert!(reported(&target(10000, 83), "is outside the level"));
+        // The level's corner is a wall
+        assert!(reported(&target(0, 0), "is inside a solid tile"));
+        let issues = check(7, |level| {
+            let spider = entity(level, "Spider", None);
+            set_field(&mut spider["fieldInstances"], "Target", json!(null));
+        });
+        assert!(reported(&issues, "Field 'Target' is not a Point"));
+    }
+
+    /// `#` is a wall, the door is the 1x2 tiles at column 1, row 1
+    fn door_reaches_edge(rows: &[&str]) -> bool {
+        let grid = Grid {
+            width: rows[0].len(),
+            height: rows.len(),
+            size: 16,
+            cells: rows
+                .iter()
+                .flat_map(|row| row.chars())
+                .map(|cell| if cell == '#' { &WALL } else { &EMPTY })
+                .collect(),
+        };
+        let door = json!({ "px": [16, 16], "width": 16, "height": 32 });
+        let player = json!({ "width": 16, "height": 24 });
+        grid.reaches_right_edge(&door, &player)
+    }
+
+    #[test]
+    fn the_player_has_to_fit_through_gaps() {
+        #[rustfmt::skip]
+        let open = [
+            "######",
+            "#.....",
+            "#.....",
+            "######",
+        ];
+        assert!(door_reaches_edge(&open));
+        // A single tile is too low for the player's 24 pixels
+        #[rustfmt::skip]
+        let low = [
+            "######",
+            "#..###",
+            "#.....",
+            "######",
+        ];
+        assert!(!door_reaches_edge(&low));
+        #[rustfmt::skip]
+        let closed = [
+            "######",
+            "#..#..",
+            "#..#..",
+            "######",
+        ];
+        assert!(!door_reaches_edge(&closed));
+    }
+}
@@ -0,0 +1,519 @@
+use crate::assets::*;
+use crate::forgiveness::{Forgiveness, LIVES, PENALTY};
+use crate::ghost::{GhostFrame, GhostRecorder};
+use crate::input::{Action, Input};
+use crate::level::{Level, Overlay};
+use crate::player::Player;
+use crate::replay::Replay;
+use crate::timing::Timed;
+
+/// Length of a single simulation tick
+pub const TICK: f32 = 1.0 / 120.0;
+/// Fade length between levels, and the default when respawning
+pub const LEVEL_FADE: f32 = 0.5;
+
+/// Splits variable frame times into fixed simulation ticks
+#[derive(Default)]
+pub struct Timestep {
+    accumulator: f32,
+    input: Input,
+}
+
+impl Timestep {
+    /// Returns the input for every tick that should run this frame.
+    /// Presses and releases are delivered to exactly one tick.
+    pub fn advance(&mut self, frame_time: f32, input: &Input) -> Vec<Input> {
+        self.input.latch(input);
+        self.accumulator = (self.accumulator + frame_time).min(0.25);
+
+        let mut ticks = Vec::new();
+        while self.accumulator >= TICK {
+            self.accumulator -= TICK;
+            ticks.push(self.input);
+            self.input.clear_edges();
+        }
+        ticks
+    }
+
+    /// How far between the last two ticks the frame is, for interpolation
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / TICK
+    }
+}
+
+/// Something the frontend might want to react to (e.g. by playing a sound)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Jump,
+    Number,
+    /// A wrong number was touched, what happens next depends on the forgiveness mode
+    Mistake,
+    GameOver,
+    NextLevel,
+    /// The player left `level` after `time` seconds of playing it
+    LevelComplete {
+        level: usize,
+        time: f32,
+    },
+}
+
+/// The whole game simulation, independent of the window
+pub struct World {
+    pub level: Level,
+    pub player: Player,
+    pub state: State,
+    pub time: Timed<f32>,
+    /// Game time since the current level was last (re)loaded
+    pub attempt_time: f32,
+    /// Run time at the end of every finished level
+    pub splits: Vec<Timed<f32>>,
+    pub events: Vec<Event>,
+    pub replay: Replay,
+    pub ghosts: GhostRecorder,
+    /// Fade length after dying or restarting, 0 respawns within the same tick
+    pub respawn_fade: f32,
+    pub forgiveness: Forgiveness,
+    /// Lives left on the current level in [`Forgiveness::Lives`], they carry over between
+    /// attempts until they run out or the next level starts
+    pub lives: u32,
+    /// The current level as it was loaded, to respawn without loading it again
+    pristine: (Level, Player),
+}
+
+impl World {
+    pub fn load(levels: &Levels, index: usize) -> Result<Option<Self>> {
+        Ok(Level::load(levels, index)?.map(|(level, player)| Self {
+            pristine: (level.clone(), player.clone()),
+            level,
+            player,
+            state: State::Playing,
+            time: Timed::default(),
+            attempt_time: 0.0,
+            splits: Vec::new(),
+            events: Vec::new(),
+            replay: Replay {
+                levels_hash: levels.hash,
+                ..Replay::default()
+            },
+            ghosts: GhostRecorder::default(),
+            respawn_fade: LEVEL_FADE,
+            forgiveness: Forgiveness::Normal,
+            lives: LIVES,
+        }))
+    }
+
+    pub fn step(&mut self, levels: &Levels, dt: f32, input: &Input) -> Result<()> {
+        self.player.save_position();
+        self.level.save_positions();
+        self.replay.record(self.level.index(), input);
+
+        if input.pressed(Action::Pause) {
+            self.state = match self.state {
+                State::Playing => State::Paused,
+                State::Paused => State::Playing,
+                state => state,
+            };
+        }
+
+        // Game time is the sum of the ticks spent playing, real time comes from `add_real_time`
+        if !matches!(self.state, State::Finished | State::Failed) {
+            self.replay.time = self.time.game;
+            self.replay.respawn_fade = self.respawn_fade;
+            self.replay.forgiveness = self.forgiveness;
+        }
+        if self.state == State::Playing {
+            self.time.game += dt;
+            self.attempt_time += dt;
+        }
+
+        let running = self.state != State::Paused && self.state != State::Finished;
+        if running {
+            self.level.update(dt);
+        }
+        if self.state == State::Playing && input.pressed(Action::Restart) {
+            self.state = State::transition(self.level.index(), self.respawn_fade);
+        }
+        if self.state == State::Playing {
+            let events = self.events.len();
+            self.player
+                .update(dt, input, &mut self.level, &mut self.events);
+            if self.events[events..].contains(&Event::Mistake) {
+                self.mistake();
+            }
+            if self.events[events..].contains(&Event::GameOver) {
+                self.state = if self.forgiveness == Forgiveness::Hardcore
+                    && self.events[events..].contains(&Event::Mistake)
+                {
+                    State::Failed
+                } else {
+                    State::transition(self.level.index(), self.respawn_fade)
+                };
+            }
+            self.ghosts.attempt.frames.push(self.ghost_frame());
+            if self.player.position().x >= self.level.size().x {
+                self.state = State::transition(self.level.index() + 1, LEVEL_FADE);
+                self.splits.push(self.time);
+                self.events.push(Event::LevelComplete {
+                    level: self.level.index(),
+                    time: self.attempt_time,
+                });
+                self.ghosts
+                    .finished
+                    .push(std::mem::take(&mut self.ghosts.attempt));
+            }
+        }
+
+        if let State::LevelTransition {
+            next_level,
+            mut timer,
+            fade,
+            mut sound_played,
+            mut loaded,
+        } = self.state
+        {
+            timer -= dt;
+
+            if timer <= fade && !sound_played {
+                if next_level != self.level.index() {
+                    self.events.push(Event::NextLevel);
+                }
+                sound_played = true;
+            }
+            if timer <= 0.0 && !loaded {
+                if next_level == self.level.index() {
+                    self.respawn();
+                    if self.lives == 0 {
+                        self.lives = LIVES;
+                    }
+                } else if let Some((level, player)) =
+                    Level::load(levels, next_level).context("Failed to load level!")?
+                {
+                    self.pristine = (level.clone(), player.clone());
+                    (self.level, self.player) = (level, player);
+                    self.lives = LIVES;
+                } else {
+                    self.state = State::Finished;
+                    return Ok(());
+                }
+                self.ghosts.attempt.frames.clear();
+                self.attempt_time = 0.0;
+                loaded = true;
+            }
+            self.state = if timer <= -fade {
+                State::Playing
+            } else {
+                State::LevelTransition {
+                    next_level,
+                    timer,
+                    fade,
+                    sound_played,
+                    loaded,
+                }
+            };
+        }
+
+        if running {
+            let frame = self.ghost_frame();
+            self.ghosts.run.frames.push(frame);
+        }
+
+        Ok(())
+    }
+
+    /// Real time is measured by the wall clock, so it keeps running through pauses, transitions
+    /// and hitches the timestep drops ticks for
+    pub fn add_real_time(&mut self, frame_time: f32) {
+        if !matches!(self.state, State::Finished | State::Failed) {
+            self.time.real += frame_time;
+        }
+    }
+
+    /// Swaps in a new version of the current level, the player stays where it was if it fits
+    pub fn reload(&mut self, levels: &Levels) -> Result<()> {
+        let Some((mut level, player)) = Level::load(levels, self.level.index())? else {
+            return Ok(());
+        };
+        self.pristine = (level.clone(), player.clone());
+        level.particles = std::mem::take(&mut self.level.particles);
+        self.level = level;
+        if self.player.collides(&self.level) {
+            self.player = player;
+        }
+        Ok(())
+    }
+
+    /// Applies the forgiveness mode to touching a wrong number
+    fn mistake(&mut self) {
+        match self.forgiveness {
+            // Losing the last life ends the attempt, the level starts over with all of them
+            Forgiveness::Lives => {
+                self.lives = self.lives.saturating_sub(1);
+                if self.lives == 0 {
+                    self.player.die(&mut self.level, &mut self.events);
+                }
+            }
+            Forgiveness::Penalty => {
+                self.time.real += PENALTY;
+                self.time.game += PENALTY;
+                self.attempt_time += PENALTY;
+                self.level
+                    .overlays
+                    .push(Overlay::new(format!("+{}s", PENALTY)));
+            }
+            _ => self.player.die(&mut self.level, &mut self.events),
+        }
+    }
+
+    /// Resets the level from the snapshot, the death explosion keeps flying
+    fn respawn(&mut self) {
+        let particles = std::mem::take(&mut self.level.particles);
+        (self.level, self.player) = self.pristine.clone();
+        self.level.particles = particles;
+    }
+
+    fn ghost_frame(&self) -> GhostFrame {
+        GhostFrame {
+            level: self.level.index(),
+            position: self.player.position(),
+            frame: self.player.frame(),
+            visible: self.state.player_visible(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::solver;
+    use crate::tile::LEVEL_LAYER;
+
+    fn step(world: &mut World, levels: &Levels, input: &Input) {
+        world.step(levels, TICK, input).unwrap();
+    }
+
+    #[test]
+    fn presses_reach_exactly_one_tick() {
+        let mut timestep = Timestep::default();
+        let held = Input::default().next(Action::Jump.mask());
+        let ticks = timestep.advance(TICK * 3.5, &held);
+        assert_eq!(ticks.len(), 3);
+        assert!(ticks[0].pressed(Action::Jump));
+        assert!(ticks.iter().all(|tick| tick.down(Action::Jump)));
+        assert!(!ticks[1..].iter().any(|tick| tick.pressed(Action::Jump)));
+        // The leftover half tick carries over into the next frame
+        let ticks = timestep.advance(TICK * 0.5, &held.next(Action::Jump.mask()));
+        assert_eq!(ticks.len(), 1);
+        assert!(!ticks[0].pressed(Action::Jump));
+    }
+
+    #[test]
+    fn presses_wait_for_the_next_tick() {
+        let mut timestep = Timestep::default();
+        // A tap within one short frame, too short for a tick of its own
+        let mut tap = Input::default();
+        tap.press(Action::Jump);
+        let released = tap.next(0);
+        assert!(timestep.advance(TICK * 0.5, &tap).is_empty());
+        let ticks = timestep.advance(TICK, &released);
+        assert_eq!(ticks.len(), 1);
+        assert!(ticks[0].pressed(Action::Jump));
+        assert!(!ticks[0].down(Action::Jump));
+        assert!(timestep
+            .advance(TICK, &released.next(0))
+            .iter()
+            .all(|tick| !tick.pressed(Action::Jump)));
+    }
+
+    #[test]
+    fn restart_respawns_after_the_fade() {
+        let levels = embedded_levels().unwrap();
+        let mut world = World::load(&levels, 0).unwrap().unwrap();
+        let start = world.player.position();
+        let mut input = Input::default();
+        for _ in 0..30 {
+            input = input.next(Action::Right.mask());
+            step(&mut world, &levels, &input);
+        }
+        assert_eq!(world.state, State::Playing);
+        assert_ne!(world.player.position(), start);
+
+        input = input.next(0);
+        input.press(Action::Restart);
+        step(&mut world, &levels, &input);
+        assert!(matches!(
+            world.state,
+            State::LevelTransition { next_level: 0, .. }
+        ));
+        let game_time = world.time.game;
+
+        let mut ticks = 0;
+        while world.state != State::Playing {
+            step(&mut world, &levels, &Input::default());
+            ticks += 1;
+            assert!(ticks < 1000, "Respawn never finished");
+        }
+        assert_eq!(world.player.position(), start);
+        assert!(world.splits.is_empty());
+        // Game time stands still through the fade
+        assert_eq!(world.time.game, game_time);
+    }
+
+    #[test]
+    fn hitches_only_lose_game_time() {
+        let levels = embedded_levels().unwrap();
+        let mut world = World::load(&levels, 0).unwrap().unwrap();
+        let mut timestep = Timestep::default();
+        world.add_real_time(1.0);
+        for input in timestep.advance(1.0, &Input::default()) {
+            step(&mut world, &levels, &input);
+        }
+        assert_eq!(world.time.real, 1.0);
+        assert!(world.time.game <= 0.25);
+    }
+
+    /// Steps until the transition after dying or restarting is over
+    fn finish_transition(world: &mut World, levels: &Levels) {
+        let mut ticks = 0;
+        while world.state != State::Playing {
+            step(world, levels, &Input::default());
+            ticks += 1;
+            assert!(ticks < 1000, "Respawn never finished");
+        }
+    }
+
+    /// Puts the player on a number that isn't next and steps once
+    fn touch_wrong_number(world: &mut World, levels: &Levels) {
+        let tracks = &world.level.tracks;
+        let rect = world
+            .level
+            .numbers
+            .iter()
+            .find(|number| tracks[number.track()].sequence.next() != Some(number.number()))
+            .unwrap()
+            .rect();
+        world.player = Player::new(rvec2(rect.x, rect.y), rvec2(16, 24));
+        step(world, levels, &Input::default());
+    }
+
+    #[test]
+    fn hardcore_only_ends_the_run_on_mistakes() {
+        let levels = embedded_levels().unwrap();
+        // Right above the spikes on the floor of the fifth level
+        let mut world = World::load(&levels, 4).unwrap().unwrap();
+        world.forgiveness = Forgiveness::Hardcore;
+        world.player = Player::new(rvec2(48, 214), rvec2(16, 24));
+        step(&mut world, &levels, &Input::default());
+        assert!(world.events.contains(&Event::GameOver));
+        assert!(matches!(
+            world.state,
+            State::LevelTransition { next_level: 4, .. }
+        ));
+
+        let mut world = World::load(&levels, 0).unwrap().unwrap();
+        world.forgiveness = Forgiveness::Hardcore;
+        touch_wrong_number(&mut world, &levels);
+        assert_eq!(world.state, State::Failed);
+    }
+
+    #[test]
+    fn lives_carry_over_until_they_run_out() {
+        let levels = embedded_levels().unwrap();
+        let mut world = World::load(&levels, 0).unwrap().unwrap();
+        world.forgiveness = Forgiveness::Lives;
+        touch_wrong_number(&mut world, &levels);
+        assert_eq!(world.lives, LIVES - 1);
+        assert_eq!(world.state, State::Playing);
+
+        // Restarting keeps the lives that are left
+        let mut input = Input::default();
+        input.press(Action::Restart);
+        step(&mut world, &levels, &input);
+        finish_transition(&mut world, &levels);
+        assert_eq!(world.lives, LIVES - 1);
+
+        // The last one ends the attempt and the level starts over with all of them
+        for _ in 1..LIVES {
+            assert_eq!(world.state, State::Playing);
+            touch_wrong_number(&mut world, &levels);
+        }
+        assert_eq!(world.lives, 0);
+        assert!(matches!(
+            world.state,
+            State::LevelTransition { next_level: 0, .. }
+        ));
+        finish_transition(&mut world, &levels);
+        assert_eq!(world.lives, LIVES);
+    }
+
+    /// The levels with the first one cut down to a short corridor with a single number,
+    /// small enough for the solver to get through quickly
+    fn corridor() -> Levels {
+        let rows = [
+            "1111111111",
+            "1000000001",
+            "1000000000",
+            "1000000000",
+            "1111111111",
+        ];
+        let mut project: Value = serde_json::from_str(include_str!("../levels.ldtk")).unwrap();
+        let level = &mut project["levels"][0];
+        level["pxWid"] = json!(rows[0].len() * 16);
+        level["pxHei"] = json!(rows.len() * 16);
+        for field in level["fieldInstances"].as_array_mut().unwrap() {
+            if field["__identifier"] == "TargetNumber" {
+                field["__value"] = json!(1);
+            }
+        }
+        for layer in level["layerInstances"].as_array_mut().unwrap() {
+            if layer["__identifier"] == LEVEL_LAYER {
+                layer["__cWid"] = json!(rows[0].len());
+                layer["__cHei"] = json!(rows.len());
+                layer["intGridCsv"] = rows
+                    .iter()
+                    .flat_map(|row| row.chars())
+                    .map(|tile| json!(tile.to_digit(10).unwrap()))
+                    .collect();
+                layer["autoLayerTiles"] = json!([]);
+                continue;
+            }
+            let entities = layer["entityInstances"].as_array_mut().unwrap();
+            entities.retain(|entity| {
+                entity["__identifier"] != "Number"
+                    || entity["fieldInstances"][0]["__value"] == "Number1"
+            });
+            for entity in entities {
+                entity["px"] = match entity["__identifier"].as_str().unwrap() {
+                    "Player" => json!([19, 40]),
+                    "Number" => json!([80, 48]),
+                    _ => json!([144, 32]),
+                };
+            }
+        }
+        parse_levels(&project.to_string(), Path::new("")).unwrap()
+    }
+
+    #[test]
+    fn solution_completes_the_level() {
+        let levels = corridor();
+        let solution = solver::solve(&levels, 0).unwrap().unwrap();
+        let mut world = World::load(&levels, 0).unwrap().unwrap();
+        for (_, input) in solution.replay.inputs() {
+            step(&mut world, &levels, &input);
+        }
+
+        assert!(!world.events.contains(&Event::GameOver));
+        assert!(world
+            .events
+            .iter()
+            .any(|event| matches!(event, Event::LevelComplete { level: 0, .. })));
+        assert_eq!(world.splits.len(), 1);
+        assert!(matches!(
+            world.state,
+            State::LevelTransition { next_level: 1, .. }
+        ));
+    }
+}